@@ -1,11 +1,24 @@
-import init, { GameState, Player } from './pkg/game_of_ur.js';
+import init, { GameState, Player, Variant, random_dice_secret, dice_commitment, verify_fair_roll } from './pkg/game_of_ur.js';
 
 let game = null;
+let computerPlayer = null;  // Player the computer moves for, or null for hot-seat play
+let computerTimer = null;
+let remote = null;  // { socket, player, secret, checkedRound, clocks } while playing on a game server
+let lastMove = { to: null, captured: false };  // Square to animate after the next render
+let eventLog = [];  // Latest game events, described
+
+const COMPUTER_DELAY = 700;
+const MCTS_MILLIS = [100, 300, 1000, 3000];  // Thinking time for each difficulty
+const WIN_BAR_DEPTH = 2;  // Search depth behind the win-probability bar
+const EVENT_LOG_LENGTH = 4;
+const CLOCK_TICK = 100;  // How often the clocks are redrawn and checked for flag-fall
+const LOW_TIME = 10000;  // Clocks turn red below this many milliseconds
 
 async function loadGame() {
     await init();
-    game = new GameState();
+    startLocalGame();
     updateUI();
+    setInterval(tickClocks, CLOCK_TICK);
 }
 
 function updateUI() {
@@ -19,6 +32,8 @@ function updateUI() {
     const lightOffEl = document.getElementById('light-pieces-off');
     const darkOffEl = document.getElementById('dark-pieces-off');
     const rollButtonEl = document.getElementById('roll-dice');
+    const undoButtonEl = document.getElementById('undo');
+    const redoButtonEl = document.getElementById('redo');
     
     if (playerNameEl) {
         playerNameEl.textContent = game.get_player_name();
@@ -43,7 +58,141 @@ function updateUI() {
         rollButtonEl.disabled = game.dice_value !== 0 || game.game_over;
     }
     
+    if (undoButtonEl) undoButtonEl.disabled = !game.can_undo;
+    if (redoButtonEl) redoButtonEl.disabled = !game.can_redo;
+    
+    updateWinBar();
     renderBoard();
+    showEventLog();
+    lastMove.to = null;
+}
+
+function startLocalGame() {
+    if (game) game.free();
+    game = new GameState();
+    game.subscribe(handleGameEvent);
+    const control = readTimeControl();
+    if (control) {
+        game.set_time_control(control.initial_ms, control.increment_ms, control.delay_ms);
+    }
+    clearEvents();
+}
+
+// The chosen time control as { initial_ms, increment_ms, delay_ms }, or null for no clock
+function readTimeControl() {
+    const controlEl = document.getElementById('time-control');
+    if (!controlEl || !controlEl.value) return null;
+    const [initial_ms, increment_ms, delay_ms] = controlEl.value.split(',').map(Number);
+    return { initial_ms, increment_ms, delay_ms };
+}
+
+function handleTimeControlChange() {
+    if (remote) return;
+    clearTimeout(computerTimer);
+    computerTimer = null;
+    startLocalGame();
+    updateUI();
+    scheduleComputerTurn();
+}
+
+// The game itself enforces the clocks: checking them often ends it as soon as the player to
+// move runs out. Online, the server does that and this only counts down between its updates
+function tickClocks() {
+    if (!game) return;
+    let light = null;
+    let dark = null;
+    let running = null;
+    if (remote) {
+        const clocks = remote.clocks;
+        if (clocks && clocks.light !== null) {
+            const elapsed = performance.now() - clocks.received;
+            light = clocks.light - (clocks.running === 'Light' ? elapsed : 0);
+            dark = clocks.dark - (clocks.running === 'Dark' ? elapsed : 0);
+            running = clocks.running;
+        }
+    } else if (game.time_control) {
+        if (game.check_time()) updateUI();
+        light = game.remaining_millis(Player.Light);
+        dark = game.remaining_millis(Player.Dark);
+        running = game.game_over ? null : (game.current_player === Player.Light ? 'Light' : 'Dark');
+    }
+    showClock('light-clock', 'Light', light, running === 'Light');
+    showClock('dark-clock', 'Dark', dark, running === 'Dark');
+}
+
+function showClock(id, name, millis, isRunning) {
+    const clockEl = document.getElementById(id);
+    if (!clockEl) return;
+    if (millis === null || millis === undefined) {
+        clockEl.textContent = '';
+        return;
+    }
+    clockEl.textContent = `${name} ${formatClock(Math.max(0, millis))}`;
+    clockEl.classList.toggle('running', isRunning);
+    clockEl.classList.toggle('low', millis < LOW_TIME);
+}
+
+// m:ss, with tenths of a second under LOW_TIME
+function formatClock(millis) {
+    if (millis < LOW_TIME) return (millis / 1000).toFixed(1);
+    const seconds = Math.floor(millis / 1000);
+    return `${Math.floor(seconds / 60)}:${String(seconds % 60).padStart(2, '0')}`;
+}
+
+// Game events arrive from the local game as it plays, or from the server online. They're
+// kept for the next render, which animates the square a piece landed on
+function handleGameEvent(event) {
+    switch (event.type) {
+        case 'PieceEntered':
+        case 'PieceMoved':
+            lastMove = { to: event.to, captured: false };
+            break;
+        case 'PieceCaptured':
+            lastMove.captured = true;
+            break;
+    }
+    eventLog.push(describeEvent(event));
+    eventLog = eventLog.slice(-EVENT_LOG_LENGTH);
+}
+
+// Players arrive as their names, e.g. { type: 'DiceRolled', player: 'Light', value: 3 }
+function describeEvent(event) {
+    const name = event.player;
+    switch (event.type) {
+        case 'DiceRolled': return `${name} rolled ${event.value}`;
+        case 'PieceEntered': return `${name} entered on ${event.to + 1}`;
+        case 'PieceMoved': return `${name} moved ${event.from + 1}→${event.to + 1}`;
+        case 'PieceCaptured': return `${name}'s piece on ${event.at + 1} was captured`;
+        case 'PieceBorneOff': return `${name} bore a piece off`;
+        case 'TurnPassed': return `${name} to play`;
+        case 'ExtraTurn': return `${name} rolls again`;
+        case 'TimeExpired': return `${name} ran out of time`;
+        case 'Forfeited': return `${name} forfeited`;
+        case 'GameWon': return `${event.winner} won`;
+        default: return event.type;
+    }
+}
+
+function clearEvents() {
+    lastMove = { to: null, captured: false };
+    eventLog = [];
+}
+
+function showEventLog() {
+    const logEl = document.getElementById('event-log');
+    if (logEl) logEl.textContent = eventLog.join(' · ');
+}
+
+function updateWinBar() {
+    const lightEl = document.getElementById('win-bar-light');
+    const darkEl = document.getElementById('win-bar-dark');
+    if (!lightEl || !darkEl) return;
+    
+    const chances = game.win_probability(WIN_BAR_DEPTH);
+    const light = Math.round(100 * chances.light);
+    lightEl.style.width = `${light}%`;
+    lightEl.textContent = `Light ${light}%`;
+    darkEl.textContent = `Dark ${100 - light}%`;
 }
 
 function renderBoard() {
@@ -52,42 +201,20 @@ function renderBoard() {
     
     boardContainer.innerHTML = '';
     
+    // Each square goes on its cell of the game's layout, so every variant's board draws
+    // itself, numbered along the route of the player to move
+    const layout = game.get_layout();
+    const route = game.get_route(game.current_player);
     const mainBoard = document.createElement('div');
     mainBoard.className = 'main-board';
-    
-    const leftSection = document.createElement('div');
-    leftSection.className = 'board-section left-section';
-    for (let row = 0; row < 3; row++) {
-        const boardRow = document.createElement('div');
-        boardRow.className = 'board-row';
-        for (let col = 0; col < 4; col++) {
-            boardRow.appendChild(createSquare(row * 4 + col));
-        }
-        leftSection.appendChild(boardRow);
-    }
-    
-    const centreSection = document.createElement('div');
-    centreSection.className = 'board-section centre-section';
-    const centreRow = document.createElement('div');
-    centreRow.className = 'board-row';
-    centreRow.appendChild(createSquare(12));
-    centreRow.appendChild(createSquare(13));
-    centreSection.appendChild(centreRow);
-    
-    const rightSection = document.createElement('div');
-    rightSection.className = 'board-section right-section';
-    for (let row = 0; row < 3; row++) {
-        const boardRow = document.createElement('div');
-        boardRow.className = 'board-row';
-        for (let col = 0; col < 2; col++) {
-            boardRow.appendChild(createSquare(14 + row * 2 + col));
-        }
-        rightSection.appendChild(boardRow);
-    }
-    
-    mainBoard.appendChild(leftSection);
-    mainBoard.appendChild(centreSection);
-    mainBoard.appendChild(rightSection);
+    mainBoard.style.gridTemplateColumns = `repeat(${Math.max(...layout.map(cell => cell.col)) + 1}, auto)`;
+    mainBoard.style.gridTemplateRows = `repeat(${Math.max(...layout.map(cell => cell.row)) + 1}, auto)`;
+    layout.forEach((cell, boardIndex) => {
+        const square = createSquare(boardIndex, route.indexOf(boardIndex) + 1);
+        square.style.gridRow = cell.row + 1;
+        square.style.gridColumn = cell.col + 1;
+        mainBoard.appendChild(square);
+    });
     
     const startAreasContainer = document.createElement('div');
     startAreasContainer.className = 'start-areas-container';
@@ -132,13 +259,15 @@ function renderBoard() {
     boardContainer.appendChild(startAreasContainer);
 }
 
-function createSquare(boardIndex) {
+// pathPos is the square's place on the mover's route, or 0 when they don't pass it
+function createSquare(boardIndex, pathPos) {
     const square = document.createElement('div');
     square.className = 'square';
     
     const squareInfo = game.get_square_info(boardIndex);
     const pieceType = squareInfo[0];
     const isValidMove = squareInfo[1];
+    const isRosette = squareInfo[2];
     
     let content = '';
     switch (pieceType) {
@@ -158,6 +287,14 @@ function createSquare(boardIndex) {
         square.className += ' valid-move';
     }
     
+    if (isRosette === 1) {
+        square.className += ' rosette';
+    }
+    
+    if (boardIndex === lastMove.to) {
+        square.className += lastMove.captured ? ' just-moved just-captured' : ' just-moved';
+    }
+    
     square.textContent = content;
     
     const squareNumber = document.createElement('span');
@@ -165,6 +302,13 @@ function createSquare(boardIndex) {
     squareNumber.textContent = boardIndex + 1;
     square.appendChild(squareNumber);
     
+    if (pathPos > 0) {
+        const routeStep = document.createElement('span');
+        routeStep.className = 'route-step';
+        routeStep.textContent = pathPos;
+        square.appendChild(routeStep);
+    }
+    
     square.onclick = () => handleSquareClick(boardIndex);
     
     return square;
@@ -172,10 +316,34 @@ function createSquare(boardIndex) {
 
 function handleRollDice() {
     if (!game || game.game_over) return;
+    if (remote) {
+        sendToServer({ type: 'roll' });
+        return;
+    }
     
-    game.roll_dice();
+    let roll;
+    try {
+        roll = game.roll_dice();
+    } catch (err) {
+        // Out of time, or the dice have run out: show why instead of a roll
+        updateUI();
+        const statusEl = document.getElementById('status');
+        if (statusEl && !game.game_over) {
+            statusEl.textContent = err.message;
+        }
+        return;
+    }
     updateUI();
     
+    if (roll === 0) {
+        const statusEl = document.getElementById('status');
+        if (statusEl) {
+            statusEl.textContent = 'Rolled 0. Turn passes.';
+        }
+        scheduleComputerTurn();
+        return;
+    }
+    
     const moves = game.get_valid_moves();
     if (moves.length === 0 && game.dice_value !== 0) {
         const statusEl = document.getElementById('status');
@@ -185,56 +353,347 @@ function handleRollDice() {
         setTimeout(() => {
             game.pass_turn();
             updateUI();
+            scheduleComputerTurn();
         }, 1000);
+        return;
     }
+    
+    scheduleComputerTurn();
 }
 
 function handleReset() {
     if (!game) return;
+    if (remote) {
+        sendToServer({ type: 'new_game' });
+        return;
+    }
     game.reset();
+    clearEvents();
     updateUI();
+    scheduleComputerTurn();
+}
+
+function handleUndo() {
+    if (!game) return;
+    if (game.undo()) {
+        // Against the computer, take back its turns as well so it's your move again
+        while (isComputerTurn() && game.can_undo) {
+            game.undo();
+        }
+        updateUI();
+    }
+}
+
+function handleRedo() {
+    if (!game) return;
+    if (game.redo()) {
+        updateUI();
+        scheduleComputerTurn();
+    }
+}
+
+function isComputerTurn() {
+    return !remote && computerPlayer !== null && !game.game_over && game.current_player === computerPlayer;
+}
+
+function scheduleComputerTurn() {
+    if (!game || computerTimer !== null || !isComputerTurn()) return;
+    computerTimer = setTimeout(playComputerStep, COMPUTER_DELAY);
+}
+
+// Rolls or moves for the computer, one step at a time so each is visible
+function playComputerStep() {
+    computerTimer = null;
+    if (!game || !isComputerTurn()) return;
+    
+    if (game.dice_value === 0) {
+        handleRollDice();
+        return;
+    }
+    
+    const depthEl = document.getElementById('computer-depth');
+    const engineEl = document.getElementById('computer-engine');
+    const level = depthEl ? parseInt(depthEl.value, 10) : 3;
+    
+    if (engineEl && engineEl.value === 'mcts') {
+        const result = game.mcts_move_timed(MCTS_MILLIS[level - 1]);
+        if (result) {
+            showComputerInfo(result);
+            tryMove(result.move.from);
+        }
+        return;
+    }
+    
+    const result = game.best_move(level);
+    if (result) {
+        tryMove(result.move.from);
+    }
+}
+
+// Shows how the Monte Carlo playouts were spread over the moves it considered
+function showComputerInfo(result) {
+    const infoEl = document.getElementById('computer-info');
+    if (!infoEl) return;
+    
+    const total = result.moves.reduce((sum, stats) => sum + stats.visits, 0) || 1;
+    const moves = result.moves.map(stats => {
+        const share = Math.round(100 * stats.visits / total);
+        const wins = Math.round(100 * stats.win_rate);
+        return `${stats.move.from}→${stats.move.to}: ${share}% of playouts, ${wins}% won`;
+    });
+    infoEl.textContent = `Computer (${result.iterations} playouts) — ${moves.join(' · ')}`;
+}
+
+function handleComputerToggle(event) {
+    computerPlayer = event.target.checked ? Player.Dark : null;
+    scheduleComputerTurn();
 }
 
 function handleStartClick(pathPos, player) {
-    if (!game || game.game_over) return;
+    if (!game || game.game_over || isComputerTurn() || isOpponentsTurn()) return;
     if (game.current_player !== player) return;
     if (game.dice_value === 0) return;
     
-    const moves = game.get_valid_moves();
-    if (!moves.includes(pathPos)) return;
-    
-    if (game.make_move(pathPos)) {
-        updateUI();
-    }
+    tryMove(pathPos);
 }
 
 function handleSquareClick(boardIndex) {
-    if (!game || game.game_over) return;
+    if (!game || game.game_over || isComputerTurn() || isOpponentsTurn()) return;
     if (game.dice_value === 0) return;
     
     const pathPos = game.board_index_to_path(boardIndex, game.current_player);
     if (pathPos === null || pathPos === undefined) return;
     
-    const moves = game.get_valid_moves();
-    if (!moves.includes(pathPos)) return;
+    tryMove(pathPos);
+}
+
+function tryMove(pathPos) {
+    const statusEl = document.getElementById('status');
     
-    if (game.make_move(pathPos)) {
+    if (remote) {
+        sendToServer({ type: 'move', from: pathPos });
+        return;
+    }
+    
+    try {
+        const outcome = game.make_move(pathPos);
         updateUI();
+        if (outcome.extra_turn && !game.game_over && statusEl) {
+            statusEl.textContent = 'Extra turn! Roll again.';
+        }
+        scheduleComputerTurn();
+    } catch (err) {
+        // Illegal move: explain why instead of ignoring the click
+        if (statusEl) {
+            statusEl.textContent = err.message;
+        }
+    }
+}
+
+// Online play: the server holds the game and checks moves, and this page shows its position
+// after every change. Watching follows a room without a seat, optionally some seconds behind
+function handleConnect(watching) {
+    if (remote) {
+        remote.socket.close();
+        return;
+    }
+    const url = document.getElementById('server-url').value;
+    const room = document.getElementById('room-name').value || 'lobby';
+    const name = document.getElementById('your-name').value || null;
+    const delay = Number(document.getElementById('watch-delay').value) || 0;
+    
+    let socket;
+    try {
+        socket = new WebSocket(url);
+    } catch (err) {
+        showStatus(`Can't connect: ${err.message}`);
+        return;
+    }
+    remote = { socket, player: null, secret: null, checkedRound: null, rolling: null };
+    clearEvents();
+    clearTimeout(computerTimer);
+    computerTimer = null;
+    
+    const join = { type: 'join', room, name, time_control: readTimeControl() };
+    socket.onopen = () => sendToServer(watching ? { type: 'watch', room, delay } : join);
+    socket.onmessage = (event) => handleServerMessage(JSON.parse(event.data));
+    socket.onerror = () => showStatus(`Can't reach ${url}`);
+    socket.onclose = () => {
+        remote = null;
+        setConnectLabels('Play Online', 'Watch');
+        startLocalGame();
+        updateUI();
+        showStatus('Disconnected from the server');
+    };
+    setConnectLabels('Leave', 'Leave');
+}
+
+function handleServerMessage(message) {
+    switch (message.type) {
+        case 'joined':
+            remote.player = message.player === 'Light' ? Player.Light : Player.Dark;
+            showComputerText(`Playing ${message.player} in room "${message.room}"`);
+            break;
+        case 'watching':
+            showComputerText(message.delay ? `Watching room "${message.room}", ${message.delay}s behind` : `Watching room "${message.room}"`);
+            break;
+        case 'state':
+            // wasm memory isn't garbage collected, so let go of the old position first
+            if (game) game.free();
+            game = GameState.from_notation(message.notation, Variant[message.variant]);
+            remote.clocks = {
+                light: message.light_time_ms,
+                dark: message.dark_time_ms,
+                running: message.clock_running,
+                received: performance.now(),
+            };
+            updateUI();
+            if (message.message) showStatus(message.message);
+            if (message.last_roll) checkRoll(message.last_roll, message);
+            if (message.fair_roll) {
+                // The position the roll is made in, to check the roll against once it's applied
+                remote.rolling = { round: message.fair_roll.round, notation: message.notation };
+                takePartInRoll(message.fair_roll);
+            }
+            break;
+        case 'event':
+            handleGameEvent(message.event);
+            break;
+        case 'error':
+            showStatus(message.message);
+            break;
+    }
+}
+
+// Every roll is made from a secret of each player (see fair_dice.rs): commit to ours, then
+// reveal it once both commitments are in
+function takePartInRoll(roll) {
+    if (remote.player === null) return;
+    const seat = remote.player === Player.Light ? 0 : 1;
+    if (!remote.secret || remote.secret.round !== roll.round) {
+        const secret = random_dice_secret();
+        const commitment = dice_commitment(secret, BigInt(roll.round));
+        remote.secret = { round: roll.round, secret, commitment, committed: false, revealed: false };
     }
+    // Another update can arrive before the server has ours, so each is only sent once
+    if (roll.commitments[seat] === null && !remote.secret.committed) {
+        remote.secret.committed = true;
+        sendToServer({ type: 'commit', commitment: remote.secret.commitment });
+    } else if (roll.commitments.every(commitment => commitment !== null) && !remote.secret.revealed) {
+        remote.secret.revealed = true;
+        sendToServer({ type: 'reveal', secret: remote.secret.secret });
+    }
+}
+
+// Checks the server's account of a finished roll, including that it kept our secret and
+// that the position in the state it arrived with is the one the roll gives
+function checkRoll(roll, state) {
+    if (remote.checkedRound === roll.round) return;
+    remote.checkedRound = roll.round;
+    const seat = remote.player === Player.Light ? 0 : 1;
+    try {
+        verify_fair_roll(roll);
+        if (remote.secret && remote.secret.round === roll.round && roll.secrets[seat] !== remote.secret.secret) {
+            throw new Error('it does not use our secret');
+        }
+        // Only when we saw the roll being made, and the game didn't end on time meanwhile
+        const rolling = remote.rolling;
+        if (rolling && rolling.round === roll.round && !state.game_over
+            && state.notation !== notationAfterRoll(rolling.notation, roll.value, Variant[state.variant])) {
+            throw new Error(`the server played a different roll than ${roll.value}`);
+        }
+    } catch (err) {
+        showStatus(`Roll ${roll.round} doesn't check out: ${err.message}`);
+    }
+}
+
+// Notation after rolling value in the position: the roll waits to be moved, or the turn
+// passes when it's 0 or nothing can move. Fields are squares, Light, Dark, turn and dice
+function notationAfterRoll(notation, value, variant) {
+    const fields = notation.split(' ');
+    if (value !== 0) {
+        fields[4] = String(value);
+        const rolled = GameState.from_notation(fields.join(' '), variant);
+        const canMove = rolled.get_valid_moves().length > 0;
+        rolled.free();
+        if (canMove) return fields.join(' ');
+    }
+    fields[3] = fields[3] === 'L' ? 'D' : 'L';
+    fields[4] = '-';
+    return fields.join(' ');
+}
+
+function sendToServer(message) {
+    if (remote && remote.socket.readyState === WebSocket.OPEN) {
+        remote.socket.send(JSON.stringify(message));
+    }
+}
+
+function isOpponentsTurn() {
+    return remote !== null && game.current_player !== remote.player;
+}
+
+function showStatus(text) {
+    const statusEl = document.getElementById('status');
+    if (statusEl) statusEl.textContent = text;
+}
+
+function showComputerText(text) {
+    const infoEl = document.getElementById('computer-info');
+    if (infoEl) infoEl.textContent = text;
+}
+
+function setConnectLabels(playText, watchText) {
+    const connectEl = document.getElementById('connect');
+    const watchEl = document.getElementById('watch');
+    if (connectEl) connectEl.textContent = playText;
+    if (watchEl) watchEl.textContent = watchText;
 }
 
 // Initialize event listeners
 document.addEventListener('DOMContentLoaded', () => {
     const rollButton = document.getElementById('roll-dice');
     const resetButton = document.getElementById('reset');
+    const undoButton = document.getElementById('undo');
+    const redoButton = document.getElementById('redo');
+    const computerToggle = document.getElementById('computer-dark');
+    const connectButton = document.getElementById('connect');
+    const watchButton = document.getElementById('watch');
+    const timeControlSelect = document.getElementById('time-control');
     
     if (rollButton) {
-        rollButton.onclick = handleRollDice;
+        rollButton.onclick = () => {
+            if (game && !isComputerTurn() && !isOpponentsTurn()) handleRollDice();
+        };
     }
     
     if (resetButton) {
         resetButton.onclick = handleReset;
     }
     
+    if (undoButton) {
+        undoButton.onclick = handleUndo;
+    }
+    
+    if (redoButton) {
+        redoButton.onclick = handleRedo;
+    }
+    
+    if (computerToggle) {
+        computerToggle.onchange = handleComputerToggle;
+    }
+    
+    if (connectButton) {
+        connectButton.onclick = () => handleConnect(false);
+    }
+    
+    if (watchButton) {
+        watchButton.onclick = () => handleConnect(true);
+    }
+    
+    if (timeControlSelect) {
+        timeControlSelect.onchange = handleTimeControlChange;
+    }
+    
     loadGame();
 });
@@ -0,0 +1,159 @@
+use crate::ai::eval::Evaluator;
+use crate::ai::expectiminimax::Expectiminimax;
+use crate::ai::mcts::{Budget, Mcts};
+use crate::ai::solver::SolvedTable;
+use crate::dice::SeededDice;
+use crate::game::{GameState, Player};
+use crate::moves::{Move, MoveError};
+
+// Anything that can pick a move for the current roll, so engines can play each other
+pub trait Agent {
+    fn name(&self) -> String;
+
+    // A legal move for the rolled dice, or None when nothing can move
+    fn choose_move(&mut self, game: &GameState) -> Option<Move>;
+}
+
+// Picks uniformly among the legal moves
+#[derive(Debug, Clone)]
+pub struct RandomAgent {
+    rng: SeededDice,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> RandomAgent {
+        RandomAgent { rng: SeededDice::new(seed) }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        Some(moves[(self.rng.next_u64() % moves.len() as u64) as usize])
+    }
+}
+
+// Takes the move with the best evaluation right after it, without looking at later rolls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyAgent {
+    pub evaluator: Evaluator,
+}
+
+impl Agent for GreedyAgent {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        let player = game.current_player();
+        game.legal_moves().into_iter().map(|mv| {
+            let mut child = game.detached();
+            child.apply_move(&mv);
+            (mv, self.evaluator.evaluate_for(&child, player))
+        }).max_by(|a, b| a.1.total_cmp(&b.1)).map(|(mv, _)| mv)
+    }
+}
+
+impl Agent for Expectiminimax {
+    fn name(&self) -> String {
+        format!("expectiminimax:{}", self.depth)
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        self.best_move(game)
+    }
+}
+
+impl Agent for Mcts {
+    fn name(&self) -> String {
+        match self.budget {
+            Budget::Iterations(iterations) => format!("mcts:{}", iterations),
+            Budget::Millis(millis) => format!("mcts:{}ms", millis),
+        }
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        self.best_move(game)
+    }
+}
+
+impl Agent for &SolvedTable {
+    fn name(&self) -> String {
+        "table".to_string()
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        self.best_move(game)
+    }
+}
+
+// A borrowed agent plays as itself, so one can be kept across several games
+impl<A: Agent + ?Sized> Agent for &mut A {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        (**self).choose_move(game)
+    }
+}
+
+// Builds an engine from a name such as random, greedy, expectiminimax:3, mcts:1000 or
+// mcts:500ms, seeding any randomness with seed. None for a name it doesn't know
+pub fn agent_from_spec(spec: &str, seed: u64) -> Option<Box<dyn Agent>> {
+    let (kind, setting) = spec.split_once(':').unwrap_or((spec, ""));
+    let agent: Box<dyn Agent> = match kind {
+        "random" => Box::new(RandomAgent::new(seed)),
+        "greedy" => Box::new(GreedyAgent::default()),
+        "expectiminimax" | "emm" => match setting {
+            "" => Box::new(Expectiminimax::default()),
+            depth => Box::new(Expectiminimax::new(depth.parse().ok()?)),
+        },
+        "mcts" => {
+            let budget = match setting.strip_suffix("ms") {
+                Some(millis) => Budget::Millis(millis.parse().ok()?),
+                None if setting.is_empty() => Mcts::default().budget,
+                None => Budget::Iterations(setting.parse().ok()?),
+            };
+            Box::new(Mcts { seed, ..Mcts::new(budget) })
+        }
+        _ => return None,
+    };
+    Some(agent)
+}
+
+// How one game between two agents went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameResult {
+    pub winner: Player,
+    pub turns: u32,  // Rolls by either player
+}
+
+// Plays the game out to the end. An agent that returns no move, or one the rules reject,
+// passes instead. Fails only if the game's dice run out first
+pub fn play_game(game: &mut GameState, light: &mut dyn Agent, dark: &mut dyn Agent) -> Result<GameResult, MoveError> {
+    let mut turns = 0;
+    while !game.game_over() {
+        if game.dice_value() == 0 {
+            game.roll_dice()?;
+            turns += 1;
+            continue;
+        }
+        let choice = match game.current_player() {
+            Player::Light => light.choose_move(game),
+            Player::Dark => dark.choose_move(game),
+        };
+        let moved = choice.is_some_and(|mv| game.make_move(mv.from).is_ok());
+        if !moved {
+            game.pass_turn();
+        }
+    }
+    Ok(GameResult { winner: game.winner().expect("finished games have a winner"), turns })
+}
@@ -0,0 +1,255 @@
+use serde::Serialize;
+
+use crate::ai::estimate::{Source, WinEstimator};
+use crate::game::{GameState, Player};
+use crate::history::Action;
+use crate::moves::Move;
+use crate::record::{GameRecord, RecordError};
+use crate::rules::RuleSet;
+
+// How much a move gave away against the best one for the same roll
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Quality {
+    Best,        // The best move, or close enough that it doesn't matter
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Quality {
+    pub fn name(self) -> &'static str {
+        match self {
+            Quality::Best => "best",
+            Quality::Inaccuracy => "inaccuracy",
+            Quality::Mistake => "mistake",
+            Quality::Blunder => "blunder",
+        }
+    }
+}
+
+// One move of the game next to the best move for the same roll
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MoveReview {
+    pub action: usize,          // Index of the move in the record's actions
+    pub player: Player,
+    pub roll: u8,
+    #[serde(rename = "move")]
+    pub mv: Move,
+    pub best: Move,
+    pub win_probability: f64,   // The mover's chance of winning after the move played
+    pub best_probability: f64,  // and after the best move
+    pub loss: f64,              // Difference between the two
+    pub quality: Quality,
+    pub forced: bool,           // The only legal move
+}
+
+// Totals for one player's moves
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct PlayerSummary {
+    pub moves: usize,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+    pub average_loss: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GameReport {
+    pub light: Option<String>,
+    pub dark: Option<String>,
+    pub winner: Option<Player>,
+    pub source: Source,  // What the win probabilities were worked out from
+    pub moves: Vec<MoveReview>,
+    pub light_summary: PlayerSummary,
+    pub dark_summary: PlayerSummary,
+}
+
+impl GameReport {
+    pub fn summary(&self, player: Player) -> &PlayerSummary {
+        match player {
+            Player::Light => &self.light_summary,
+            Player::Dark => &self.dark_summary,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("reports serialize")
+    }
+
+    // Copy of the record with a comment after each move that wasn't the best
+    pub fn annotate(&self, record: &GameRecord) -> GameRecord {
+        let mut annotated = record.clone();
+        for review in &self.moves {
+            if review.quality == Quality::Best {
+                continue;
+            }
+            let comment = format!(
+                "{}: {:.0}% to win, {}-{} keeps {:.0}%",
+                review.quality.name(),
+                100.0 * review.win_probability,
+                review.best.from,
+                review.best.to,
+                100.0 * review.best_probability,
+            );
+            annotated.comments.push((review.action + 1, comment));
+        }
+        annotated.comments.sort_by_key(|(after, _)| *after);
+        annotated
+    }
+}
+
+// Post-game review: replays a record and labels each move by how much of the mover's
+// chance of winning it lost against the best alternative
+#[derive(Debug)]
+pub struct Analysis {
+    pub estimator: WinEstimator,
+    pub inaccuracy: f64,  // Smallest loss for each label
+    pub mistake: f64,
+    pub blunder: f64,
+}
+
+impl Analysis {
+    pub fn new(estimator: WinEstimator) -> Analysis {
+        Analysis { estimator, inaccuracy: 0.02, mistake: 0.05, blunder: 0.10 }
+    }
+
+    pub fn quality(&self, loss: f64) -> Quality {
+        if loss >= self.blunder {
+            Quality::Blunder
+        } else if loss >= self.mistake {
+            Quality::Mistake
+        } else if loss >= self.inaccuracy {
+            Quality::Inaccuracy
+        } else {
+            Quality::Best
+        }
+    }
+
+    pub fn analyze(&self, record: &GameRecord) -> Result<GameReport, RecordError> {
+        self.analyze_with(record, RuleSet::for_variant(record.variant))
+    }
+
+    // Analyzes the record played under the given rules, such as a solved table's
+    pub fn analyze_with(&self, record: &GameRecord, rules: RuleSet) -> Result<GameReport, RecordError> {
+        // Replay to check the record, then step back through it from the start
+        let mut game = record.replay_with(rules)?;
+        while game.undo().is_some() {}
+
+        let mut moves = Vec::new();
+        let mut index = 0;
+        while let Some(action) = game.redo() {
+            if let Action::Move { mv } = action {
+                game.undo();
+                moves.push(self.review(index, &game, mv));
+                game.redo();
+            }
+            index += 1;
+        }
+
+        Ok(GameReport {
+            light: record.light.clone(),
+            dark: record.dark.clone(),
+            winner: game.winner(),
+            source: self.estimator.source(game.rules()),
+            light_summary: summarize(&moves, Player::Light),
+            dark_summary: summarize(&moves, Player::Dark),
+            moves,
+        })
+    }
+
+    fn review(&self, action: usize, game: &GameState, mv: Move) -> MoveReview {
+        let values = self.estimator.move_values(game);
+        let played = values.iter().find(|(candidate, _)| candidate.from == mv.from).map_or(0.0, |(_, value)| *value);
+        let (best, best_probability) = values.iter().copied()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((mv, played));
+        let loss = (best_probability - played).max(0.0);
+
+        MoveReview {
+            action,
+            player: game.current_player(),
+            roll: game.dice_value(),
+            mv,
+            best,
+            win_probability: played,
+            best_probability,
+            loss,
+            quality: self.quality(loss),
+            forced: values.len() == 1,
+        }
+    }
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Analysis::new(WinEstimator::default())
+    }
+}
+
+fn summarize(moves: &[MoveReview], player: Player) -> PlayerSummary {
+    let mut summary = PlayerSummary::default();
+    let mut total_loss = 0.0;
+    for review in moves.iter().filter(|review| review.player == player) {
+        summary.moves += 1;
+        total_loss += review.loss;
+        match review.quality {
+            Quality::Best => {}
+            Quality::Inaccuracy => summary.inaccuracies += 1,
+            Quality::Mistake => summary.mistakes += 1,
+            Quality::Blunder => summary.blunders += 1,
+        }
+    }
+    if summary.moves > 0 {
+        summary.average_loss = total_loss / summary.moves as f64;
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::solver::{SolveOptions, SolvedTable};
+
+    // Light walks a piece up the board instead of capturing Dark's last piece, which then
+    // bears off
+    const BLUNDER: &str = "[Variant \"Finkel\"]\n[Position \"L12L3D2 0/0 0/1 L -\"]\n[Result \"0-1\"]\n\nL2 4-6 D3 12-15";
+
+    fn two_pieces() -> RuleSet {
+        let mut rules = RuleSet::finkel();
+        rules.pieces = 2;
+        rules
+    }
+
+    #[test]
+    fn a_missed_capture_is_a_blunder() {
+        let record: GameRecord = BLUNDER.parse().unwrap();
+        let table = SolvedTable::solve(two_pieces(), &SolveOptions::default()).unwrap();
+        let report = Analysis::new(WinEstimator::with_table(table)).analyze_with(&record, two_pieces()).unwrap();
+        assert_eq!(report.source, Source::Table);
+        assert_eq!(report.winner, Some(Player::Dark));
+
+        let [light, dark] = &report.moves[..] else {
+            panic!("expected two moves, found {:?}", report.moves);
+        };
+        assert_eq!(light.quality, Quality::Blunder);
+        assert!(light.best.captures);
+        assert!(light.loss >= 0.10 && light.best_probability > light.win_probability);
+        assert!(dark.forced);
+        assert_eq!(dark.quality, Quality::Best);
+        assert_eq!(report.summary(Player::Light).blunders, 1);
+        assert_eq!(report.summary(Player::Dark).moves, 1);
+
+        let annotated = report.annotate(&record);
+        assert_eq!(annotated.comments.len(), 1);
+        assert!(annotated.comments[0].1.starts_with("blunder"));
+    }
+
+    #[test]
+    fn search_flags_the_same_move() {
+        let record: GameRecord = BLUNDER.parse().unwrap();
+        let report = Analysis::default().analyze_with(&record, two_pieces()).unwrap();
+        assert_eq!(report.source, Source::Search);
+        assert_ne!(report.moves[0].quality, Quality::Best);
+        assert!(report.moves[0].best.captures);
+    }
+}
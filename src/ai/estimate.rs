@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::ai::expectiminimax::Expectiminimax;
+use crate::ai::mcts::playout;
+use crate::ai::solver::SolvedTable;
+use crate::dice::SeededDice;
+use crate::game::{GameState, Player};
+use crate::moves::Move;
+use crate::rules::RuleSet;
+
+// Estimates kept before the cache starts over
+const CACHE_LIMIT: usize = 1 << 16;
+
+// What an estimate was worked out from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Source {
+    Result,    // The game is over
+    Table,     // A solved table: exact under perfect play
+    Search,    // Expectiminimax with the heuristic evaluation, rescaled to 0..1
+    Rollouts,  // Share of random playouts won
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WinEstimate {
+    pub player: Player,    // The player to move
+    pub probability: f64,  // Their chance of winning, 0 to 1
+    pub source: Source,
+}
+
+impl WinEstimate {
+    // The same estimate for either player
+    pub fn for_player(&self, player: Player) -> f64 {
+        if player == self.player { self.probability } else { 1.0 - self.probability }
+    }
+}
+
+// Estimates the chance that the player to move wins, using the best method available: a
+// solved table for the game's rules, then search, then random playouts. Each position's
+// estimate is remembered, so set the fields before the first estimate
+#[derive(Debug)]
+pub struct WinEstimator {
+    pub table: Option<SolvedTable>,
+    pub search: Option<Expectiminimax>,  // None to fall back to playouts
+    pub rollouts: u32,
+    pub seed: u64,                       // Seed for the playouts
+    cache: Mutex<Cache>,
+}
+
+// Estimates by position key, for one rule set at a time
+#[derive(Debug, Default)]
+struct Cache {
+    rules: Option<RuleSet>,
+    estimates: HashMap<u64, WinEstimate>,
+}
+
+impl WinEstimator {
+    // The playouts are seeded the same way every time, so a position always gets the same
+    // estimate
+    pub fn new() -> WinEstimator {
+        WinEstimator {
+            table: None,
+            search: Some(Expectiminimax::new(2)),
+            rollouts: 1000,
+            seed: 0,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub fn with_table(table: SolvedTable) -> WinEstimator {
+        WinEstimator { table: Some(table), ..WinEstimator::new() }
+    }
+
+    pub fn win_probability(&self, game: &GameState) -> f64 {
+        self.estimate(game).probability
+    }
+
+    pub fn estimate(&self, game: &GameState) -> WinEstimate {
+        let player = game.current_player();
+        if let Some(winner) = game.winner() {
+            let probability = if winner == player { 1.0 } else { 0.0 };
+            return WinEstimate { player, probability, source: Source::Result };
+        }
+
+        let key = game.position_key().ok();
+        if let Some(estimate) = key.and_then(|key| self.cached(game.rules(), key)) {
+            return estimate;
+        }
+        let estimate = self.work_out(game);
+        if let Some(key) = key {
+            self.remember(game.rules(), key, estimate);
+        }
+        estimate
+    }
+
+    // A table only answers for its own rules; other games fall back to search or playouts
+    fn work_out(&self, game: &GameState) -> WinEstimate {
+        let player = game.current_player();
+        let estimate = |probability, source| WinEstimate { player, probability, source };
+        if let Some(probability) = self.table.as_ref().and_then(|table| table.win_probability(game)) {
+            return estimate(probability, Source::Table);
+        }
+        match &self.search {
+            Some(search) => estimate((search.evaluate(game) + 1.0) / 2.0, Source::Search),
+            None => estimate(self.rollout(game), Source::Rollouts),
+        }
+    }
+
+    fn cached(&self, rules: &RuleSet, key: u64) -> Option<WinEstimate> {
+        let cache = self.cache.lock().unwrap();
+        if cache.rules.as_ref() != Some(rules) {
+            return None;
+        }
+        cache.estimates.get(&key).copied()
+    }
+
+    fn remember(&self, rules: &RuleSet, key: u64, estimate: WinEstimate) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.rules.as_ref() != Some(rules) || cache.estimates.len() >= CACHE_LIMIT {
+            cache.rules = Some(rules.clone());
+            cache.estimates.clear();
+        }
+        cache.estimates.insert(key, estimate);
+    }
+
+    // Each legal move for the current roll with the mover's chance of winning after it
+    pub fn move_values(&self, game: &GameState) -> Vec<(Move, f64)> {
+        if game.game_over() || game.dice_value() == 0 {
+            return Vec::new();
+        }
+        let player = game.current_player();
+        game.legal_moves().into_iter().map(|mv| {
+            let mut child = game.detached();
+            child.apply_move(&mv);
+            (mv, self.estimate(&child).for_player(player))
+        }).collect()
+    }
+
+    // Where estimates of unfinished games under these rules come from
+    pub fn source(&self, rules: &RuleSet) -> Source {
+        match (&self.table, &self.search) {
+            (Some(table), _) if table.rules() == rules => Source::Table,
+            (_, Some(_)) => Source::Search,
+            _ => Source::Rollouts,
+        }
+    }
+
+    fn rollout(&self, game: &GameState) -> f64 {
+        let player = game.current_player();
+        let mut rng = SeededDice::new(self.seed);
+        let rollouts = self.rollouts.max(1);
+        let wins = (0..rollouts).filter(|_| playout(game.detached(), &mut rng) == player).count();
+        wins as f64 / rollouts as f64
+    }
+}
+
+impl GameState {
+    // Chance that the player to move wins, estimated by a short search. Callers asking
+    // again and again, e.g. on every redraw, should keep a WinEstimator instead
+    pub fn win_probability(&self) -> f64 {
+        WinEstimator::new().win_probability(self)
+    }
+}
+
+impl Default for WinEstimator {
+    fn default() -> Self {
+        WinEstimator::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::solver::SolveOptions;
+
+    // Every position of a game of random moves, before and after each roll
+    fn positions(rules: RuleSet, seed: u64) -> Vec<GameState> {
+        let mut game = GameState::with_dice(rules, Box::new(SeededDice::new(seed)));
+        let mut picks = SeededDice::new(seed + 1);
+        let mut positions = Vec::new();
+        while !game.game_over() {
+            positions.push(game.detached());
+            game.roll_dice().unwrap();
+            positions.push(game.detached());
+            let moves = game.legal_moves();
+            if moves.is_empty() {
+                game.pass_turn();
+            } else {
+                game.apply_move(&moves[(picks.next_u64() % moves.len() as u64) as usize]);
+            }
+        }
+        positions.push(game);
+        positions
+    }
+
+    #[test]
+    fn estimates_are_probabilities() {
+        let rollouts = WinEstimator { search: None, rollouts: 50, ..WinEstimator::new() };
+        for estimator in [WinEstimator::new(), rollouts] {
+            for game in positions(RuleSet::finkel(), 5).iter().step_by(3) {
+                let estimate = estimator.estimate(game);
+                assert!((0.0..=1.0).contains(&estimate.probability), "{} {:?}", game.to_notation(), estimate);
+                assert_eq!(estimate.player, game.current_player());
+                for (_, value) in estimator.move_values(game) {
+                    assert!((0.0..=1.0).contains(&value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn estimates_repeat() {
+        let game = GameState::from_notation(RuleSet::finkel(), "4L3D11 5/1 6/0 D -").unwrap();
+        let estimator = WinEstimator { search: None, rollouts: 200, ..WinEstimator::new() };
+        let first = estimator.estimate(&game);
+        assert_eq!(first.source, Source::Rollouts);
+        assert_eq!(estimator.estimate(&game), first);
+        assert_eq!(WinEstimator { search: None, rollouts: 200, ..WinEstimator::new() }.estimate(&game), first);
+    }
+
+    #[test]
+    fn a_table_gives_the_solved_values() {
+        let mut rules = RuleSet::finkel();
+        rules.pieces = 2;
+        let table = SolvedTable::solve(rules.clone(), &SolveOptions::default()).unwrap();
+        let solved: Vec<(GameState, f64)> = positions(rules.clone(), 9).into_iter()
+            .map(|game| {
+                let value = table.win_probability(&game).unwrap();
+                (game, value)
+            })
+            .collect();
+
+        let estimator = WinEstimator::with_table(table);
+        assert_eq!(estimator.source(&rules), Source::Table);
+        for (game, value) in &solved {
+            let estimate = estimator.estimate(game);
+            assert_eq!(estimate.probability, *value);
+            let expected = if game.game_over() { Source::Result } else { Source::Table };
+            assert_eq!(estimate.source, expected);
+        }
+
+        // Games under other rules fall back to search
+        assert_eq!(estimator.source(&RuleSet::finkel()), Source::Search);
+        let other = GameState::new(RuleSet::finkel());
+        assert_eq!(estimator.estimate(&other).source, Source::Search);
+    }
+}
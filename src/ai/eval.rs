@@ -0,0 +1,89 @@
+use crate::game::{GameState, Player};
+
+// Value of a won game; heuristic scores stay strictly between -WIN and WIN
+pub const WIN: f64 = 1.0;
+
+// Heuristic evaluation, as a weighted count of pieces. The weights are public for tuning
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluator {
+    pub piece_off: f64,   // Each piece borne off
+    pub progress: f64,    // A piece on its last square, scaled down for pieces further back
+    pub rosette: f64,     // A piece resting on a rosette that can't be captured
+    pub danger: f64,      // A piece the opponent's next roll can capture, scaled by the chance
+                          // of that roll and by how far the piece has come
+}
+
+impl Evaluator {
+    // Light's point of view: WIN when Light has won, -WIN when Dark has won
+    // and the squashed score difference in between
+    pub fn evaluate(&self, game: &GameState) -> f64 {
+        match game.winner() {
+            Some(Player::Light) => WIN,
+            Some(Player::Dark) => -WIN,
+            None => {
+                let score = self.score(game, Player::Light) - self.score(game, Player::Dark);
+                WIN * (score / game.rules().pieces.max(1) as f64).tanh()
+            }
+        }
+    }
+
+    // Evaluation from the given player's point of view
+    pub fn evaluate_for(&self, game: &GameState, player: Player) -> f64 {
+        match player {
+            Player::Light => self.evaluate(game),
+            Player::Dark => -self.evaluate(game),
+        }
+    }
+
+    // One player's weighted piece count
+    pub fn score(&self, game: &GameState, player: Player) -> f64 {
+        let rules = game.rules();
+        let route = rules.route(player);
+        let opponent = player.opponent();
+        let opponent_route = rules.route(opponent);
+        let probabilities = rules.dice.probabilities();
+
+        let mut score = self.piece_off * game.pieces_off(player) as f64;
+        for (path_pos, &board_index) in route.squares().iter().enumerate().map(|(i, index)| (i + 1, index)) {
+            if game.board()[board_index].piece != Some(player) {
+                continue;
+            }
+            let travelled = path_pos as f64 / route.len() as f64;
+            score += self.progress * travelled;
+
+            let safe = rules.safe_rosettes && game.board()[board_index].rosette;
+            if safe {
+                score += self.rosette;
+                continue;
+            }
+
+            // Chance that the opponent's next roll lands exactly on this square
+            let Some(target) = opponent_route.path_position(board_index) else {
+                continue;
+            };
+            let hit: f64 = (1..=target.min(probabilities.len() - 1))
+                .filter(|&distance| {
+                    let from = target - distance;
+                    match opponent_route.board_index(from) {
+                        Some(index) => game.board()[index].piece == Some(opponent),
+                        None => game.pieces_in_start(opponent) > 0,
+                    }
+                })
+                .map(|distance| probabilities[distance])
+                .sum();
+            score -= self.danger * hit * travelled;
+        }
+        score
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator {
+            piece_off: 1.0,
+            progress: 0.6,
+            rosette: 0.15,
+            danger: 0.4,
+        }
+    }
+}
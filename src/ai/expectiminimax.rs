@@ -0,0 +1,359 @@
+use serde::Serialize;
+
+use crate::ai::eval::{Evaluator, WIN};
+use crate::game::{GameState, Player};
+use crate::moves::Move;
+
+// How chance nodes cut off outcomes that can't change the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pruning {
+    None,
+    Star1,  // Narrow each roll's window using the bounds of the rolls still to come
+    Star2,  // Star1, after probing one move per roll for tighter bounds. The probes cost
+            // about as much as they save with the default evaluator
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SearchResult {
+    #[serde(rename = "move")]
+    pub mv: Move,
+    pub value: f64,   // Expected evaluation for the player to move, -1 (lost) to 1 (won)
+    pub nodes: u64,   // Positions visited
+}
+
+// Expectiminimax over the dice. Light maximises and Dark minimises the evaluation, with a
+// chance node averaging over every roll before each turn. Depth counts plies: moves,
+// passes and turns lost to a roll of 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Expectiminimax {
+    pub depth: u32,
+    pub evaluator: Evaluator,
+    pub pruning: Pruning,
+}
+
+impl Expectiminimax {
+    pub fn new(depth: u32) -> Expectiminimax {
+        Expectiminimax { depth, evaluator: Evaluator::default(), pruning: Pruning::Star1 }
+    }
+
+    pub fn best_move(&self, game: &GameState) -> Option<Move> {
+        self.search(game).map(|result| result.mv)
+    }
+
+    // Searches the current roll's moves. None before rolling, once the game is over or
+    // when no move is legal
+    pub fn search(&self, game: &GameState) -> Option<SearchResult> {
+        if game.game_over() || game.dice_value() == 0 {
+            return None;
+        }
+        let mut search = Search { config: self, nodes: 0 };
+        let player = game.current_player();
+        let mut best: Option<(Move, f64)> = None;
+        let (mut alpha, mut beta) = (-WIN, WIN);
+
+        for (mv, child) in search.ordered_children(game) {
+            let value = search.after_move(&child, self.depth.max(1) - 1, alpha, beta);
+            let better = match best {
+                None => true,
+                Some((_, best_value)) => maximizes(player) && value > best_value || !maximizes(player) && value < best_value,
+            };
+            if better {
+                best = Some((mv, value));
+                // Later moves only need to show they are worse, so narrow the window
+                if maximizes(player) {
+                    alpha = alpha.max(value);
+                } else {
+                    beta = beta.min(value);
+                }
+            }
+        }
+
+        best.map(|(mv, value)| SearchResult {
+            mv,
+            value: if maximizes(player) { value } else { -value },
+            nodes: search.nodes,
+        })
+    }
+
+    // Expected evaluation for the player to move, before or after their roll, searching
+    // depth plies ahead
+    pub fn evaluate(&self, game: &GameState) -> f64 {
+        let mut search = Search { config: self, nodes: 0 };
+        let value = if game.game_over() || self.depth == 0 {
+            self.evaluator.evaluate(game)
+        } else if game.dice_value() == 0 {
+            search.chance(game, self.depth, -WIN, WIN)
+        } else {
+            search.after_roll(game, self.depth, -WIN, WIN)
+        };
+        if maximizes(game.current_player()) { value } else { -value }
+    }
+}
+
+impl Default for Expectiminimax {
+    fn default() -> Self {
+        Expectiminimax::new(3)
+    }
+}
+
+fn maximizes(player: Player) -> bool {
+    player == Player::Light
+}
+
+// Values are from Light's point of view and fail soft: a value at or below alpha is an
+// upper bound of the true value, and one at or above beta a lower bound
+struct Search<'a> {
+    config: &'a Expectiminimax,
+    nodes: u64,
+}
+
+impl Search<'_> {
+    // Position after a move: the game may be over, otherwise the next turn starts
+    fn after_move(&mut self, game: &GameState, depth: u32, alpha: f64, beta: f64) -> f64 {
+        if game.game_over() {
+            self.nodes += 1;
+            return self.config.evaluator.evaluate(game);
+        }
+        self.chance(game, depth, alpha, beta)
+    }
+
+    // Before the roll: the expected value over each roll
+    fn chance(&mut self, game: &GameState, depth: u32, alpha: f64, beta: f64) -> f64 {
+        self.nodes += 1;
+        if depth == 0 {
+            return self.config.evaluator.evaluate(game);
+        }
+
+        let outcomes = game.rules().dice.outcomes();
+        let children: Vec<(f64, GameState)> = outcomes.iter().map(|&(value, probability)| {
+            let mut child = game.detached();
+            child.set_roll(value);
+            (probability, child)
+        }).collect();
+
+        if self.config.pruning == Pruning::None {
+            return children.iter()
+                .map(|(probability, child)| probability * self.after_roll(child, depth, -WIN, WIN))
+                .sum();
+        }
+
+        // Known bounds on each roll's value, tightened as rolls are probed and searched
+        let mut lower = vec![-WIN; children.len()];
+        let mut upper = vec![WIN; children.len()];
+        let expected = |bounds: &[f64]| -> f64 {
+            children.iter().zip(bounds).map(|((probability, _), bound)| probability * bound).sum()
+        };
+
+        if self.config.pruning == Pruning::Star2 {
+            for (i, (probability, child)) in children.iter().enumerate() {
+                // A roll of 0 or a forced pass has no move to probe
+                if child.dice_value() == 0 || child.legal_moves().is_empty() {
+                    continue;
+                }
+                let (low, high) = window(i, *probability, &lower, &upper, &children, alpha, beta);
+                let probe = self.probe(child, depth, low, high);
+                // The first move bounds the roll from the mover's side, unless it failed
+                // the wrong way and only bounds itself
+                if maximizes(child.current_player()) && probe > low {
+                    lower[i] = probe;
+                } else if !maximizes(child.current_player()) && probe < high {
+                    upper[i] = probe;
+                }
+                if expected(&lower) >= beta {
+                    return expected(&lower);
+                }
+                if expected(&upper) <= alpha {
+                    return expected(&upper);
+                }
+            }
+        }
+
+        for (i, (probability, child)) in children.iter().enumerate() {
+            if expected(&lower) >= beta {
+                return expected(&lower);
+            }
+            if expected(&upper) <= alpha {
+                return expected(&upper);
+            }
+            let (low, high) = window(i, *probability, &lower, &upper, &children, alpha, beta);
+            let value = if low < high { self.after_roll(child, depth, low, high) } else { lower[i] };
+            // Cut off only when the roll's own window failed; a value at one of the roll's
+            // known bounds is exact
+            if value <= low && low > lower[i] {
+                upper[i] = value;
+                return expected(&upper);
+            }
+            if value >= high && high < upper[i] {
+                lower[i] = value;
+                return expected(&lower);
+            }
+            lower[i] = value;
+            upper[i] = value;
+        }
+        expected(&lower)
+    }
+
+    // After the roll: a roll of 0 hands the turn straight over
+    fn after_roll(&mut self, game: &GameState, depth: u32, alpha: f64, beta: f64) -> f64 {
+        if game.dice_value() == 0 {
+            return self.chance(game, depth - 1, alpha, beta);
+        }
+        self.decision(game, depth, alpha, beta)
+    }
+
+    // The player to move picks their best move, or passes when nothing can move
+    fn decision(&mut self, game: &GameState, depth: u32, mut alpha: f64, mut beta: f64) -> f64 {
+        self.nodes += 1;
+        let children = self.ordered_children(game);
+        if children.is_empty() {
+            let mut child = game.detached();
+            child.pass_turn();
+            return self.chance(&child, depth - 1, alpha, beta);
+        }
+
+        let maximizing = maximizes(game.current_player());
+        let mut best = if maximizing { -WIN } else { WIN };
+        for (_, child) in children {
+            let value = self.after_move(&child, depth - 1, alpha, beta);
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    // Value of the most promising move alone: a bound on the position from the mover's side
+    fn probe(&mut self, game: &GameState, depth: u32, alpha: f64, beta: f64) -> f64 {
+        self.nodes += 1;
+        match self.ordered_children(game).into_iter().next() {
+            Some((_, child)) => self.after_move(&child, depth - 1, alpha, beta),
+            None => self.decision(game, depth, alpha, beta),
+        }
+    }
+
+    // Legal moves with the positions they lead to, most promising first for the mover
+    fn ordered_children(&mut self, game: &GameState) -> Vec<(Move, GameState)> {
+        let evaluator = &self.config.evaluator;
+        let mut children: Vec<(Move, GameState, f64)> = game.legal_moves().into_iter().map(|mv| {
+            let mut child = game.detached();
+            child.apply_move(&mv);
+            let value = evaluator.evaluate_for(&child, game.current_player());
+            (mv, child, value)
+        }).collect();
+        children.sort_by(|a, b| b.2.total_cmp(&a.2));
+        children.into_iter().map(|(mv, child, _)| (mv, child)).collect()
+    }
+}
+
+// Window for searching roll i, given the bounds of the other rolls: below low the chance
+// node can't reach alpha whatever the others turn out to be, and above high it beats beta
+fn window(
+    i: usize,
+    probability: f64,
+    lower: &[f64],
+    upper: &[f64],
+    children: &[(f64, GameState)],
+    alpha: f64,
+    beta: f64,
+) -> (f64, f64) {
+    let mut others_lower = 0.0;
+    let mut others_upper = 0.0;
+    for (j, (p, _)) in children.iter().enumerate() {
+        if j != i {
+            others_lower += p * lower[j];
+            others_upper += p * upper[j];
+        }
+    }
+    let low = ((alpha - others_upper) / probability).max(lower[i]);
+    let high = ((beta - others_lower) / probability).min(upper[i]);
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::SeededDice;
+    use crate::rules::RuleSet;
+
+    // Positions after a roll with a choice of moves, from games of random moves
+    fn sample_positions(rules: RuleSet, games: u64) -> Vec<GameState> {
+        let mut positions = Vec::new();
+        for seed in 0..games {
+            let mut game = GameState::with_dice(rules.clone(), Box::new(SeededDice::new(seed)));
+            let mut picks = SeededDice::new(seed + 1000);
+            let mut turn = 0;
+            while !game.game_over() {
+                game.roll_dice().unwrap();
+                let moves = game.legal_moves();
+                if moves.is_empty() {
+                    game.pass_turn();
+                    continue;
+                }
+                turn += 1;
+                if moves.len() > 1 && turn % 4 == 0 {
+                    positions.push(game.detached());
+                }
+                let mv = moves[(picks.next_u64() % moves.len() as u64) as usize];
+                game.apply_move(&mv);
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn pruning_keeps_the_unpruned_values() {
+        for rules in [RuleSet::finkel(), RuleSet::blitz()] {
+            for game in sample_positions(rules, 3) {
+                let full = Expectiminimax { pruning: Pruning::None, ..Expectiminimax::new(3) };
+                let expected = full.search(&game).unwrap().value;
+                let mut before_roll = game.detached();
+                before_roll.pass_turn();
+                let expected_before = full.evaluate(&before_roll);
+                for pruning in [Pruning::Star1, Pruning::Star2] {
+                    let pruned = Expectiminimax { pruning, ..full };
+                    let value = pruned.search(&game).unwrap().value;
+                    assert!((value - expected).abs() < 1e-9, "{} {:?}: {} vs {}", game.to_notation(), pruning, value, expected);
+                    let value = pruned.evaluate(&before_roll);
+                    assert!((value - expected_before).abs() < 1e-9, "{} {:?} before the roll", before_roll.to_notation(), pruning);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pruning_visits_fewer_positions() {
+        let game = sample_positions(RuleSet::finkel(), 1).remove(0);
+        let nodes = |pruning| Expectiminimax { pruning, ..Expectiminimax::new(3) }.search(&game).unwrap().nodes;
+        assert!(nodes(Pruning::Star1) < nodes(Pruning::None));
+    }
+
+    #[test]
+    fn a_winning_bear_off_scores_as_won() {
+        // Light's last piece is two squares from home
+        let game = GameState::from_notation(RuleSet::finkel(), "15L4 0/6 7/0 L 2").unwrap();
+        let result = Expectiminimax::new(3).search(&game).unwrap();
+        assert!(result.mv.bears_off);
+        assert_eq!(result.value, WIN);
+    }
+
+    #[test]
+    fn the_search_captures_a_piece_about_to_win() {
+        // Dark's last piece is near home and Light's roll of 2 reaches it, or brings on a piece
+        for notation in ["13L3D2 6/0 0/6 L 2", "13D3L2 0/6 6/0 D 2"] {
+            let game = GameState::from_notation(RuleSet::finkel(), notation).unwrap();
+            for pruning in [Pruning::None, Pruning::Star1, Pruning::Star2] {
+                for depth in 1..=3 {
+                    let mv = Expectiminimax { pruning, ..Expectiminimax::new(depth) }.best_move(&game).unwrap();
+                    assert!(mv.captures, "{} {:?} depth {}", notation, pruning, depth);
+                }
+            }
+        }
+    }
+}
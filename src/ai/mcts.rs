@@ -0,0 +1,288 @@
+use serde::Serialize;
+
+use crate::clock::now_millis;
+use crate::dice::SeededDice;
+use crate::game::{GameState, Player};
+use crate::moves::Move;
+
+// How long a search runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget {
+    Iterations(u32),
+    Millis(f64),  // Wall-clock time; at least one iteration always runs
+}
+
+// Visit statistics of one candidate move at the root
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MoveStats {
+    #[serde(rename = "move")]
+    pub mv: Move,
+    pub visits: u32,
+    pub win_rate: f64,  // Share of playouts through this move won by the player to move
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MctsResult {
+    #[serde(rename = "move")]
+    pub mv: Move,              // The most visited move
+    pub moves: Vec<MoveStats>, // Every legal move, most visited first
+    pub iterations: u32,
+}
+
+// Monte Carlo tree search with UCT at the players' choices and sampled rolls at chance
+// nodes, scoring positions by random playouts to the end of the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mcts {
+    pub budget: Budget,
+    pub exploration: f64,  // UCT exploration constant
+    pub seed: u64,         // Seed for the sampled rolls and playouts
+}
+
+impl Mcts {
+    pub fn new(budget: Budget) -> Mcts {
+        Mcts { budget, exploration: std::f64::consts::SQRT_2, seed: SeededDice::from_entropy().seed }
+    }
+
+    pub fn best_move(&self, game: &GameState) -> Option<Move> {
+        self.search(game).map(|result| result.mv)
+    }
+
+    // Searches the current roll's moves. None before rolling, once the game is over or
+    // when no move is legal
+    pub fn search(&self, game: &GameState) -> Option<MctsResult> {
+        if game.game_over() || game.dice_value() == 0 || game.legal_moves().is_empty() {
+            return None;
+        }
+
+        let mut tree = Tree { nodes: vec![Node::new(game.detached(), None)], rng: SeededDice::new(self.seed) };
+        let started = now_millis();
+        let mut iterations = 0;
+        loop {
+            tree.iterate(self.exploration);
+            iterations += 1;
+            let done = match self.budget {
+                Budget::Iterations(limit) => iterations >= limit,
+                Budget::Millis(limit) => now_millis() - started >= limit,
+            };
+            if done {
+                break;
+            }
+        }
+
+        let player = game.current_player();
+        let mut moves: Vec<MoveStats> = tree.nodes[0].children.iter().map(|&child| {
+            let node = &tree.nodes[child];
+            MoveStats {
+                mv: node.mv.expect("root children are moves"),
+                visits: node.visits,
+                win_rate: if node.visits == 0 { 0.0 } else { node.wins(player) / node.visits as f64 },
+            }
+        }).collect();
+        moves.sort_by(|a, b| b.visits.cmp(&a.visits).then(b.win_rate.total_cmp(&a.win_rate)));
+
+        Some(MctsResult { mv: moves[0].mv, moves, iterations })
+    }
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts::new(Budget::Iterations(2000))
+    }
+}
+
+struct Node {
+    game: GameState,
+    mv: Option<Move>,       // Move that led here from a choice, None after a roll or pass
+    children: Vec<usize>,
+    expanded: bool,
+    visits: u32,
+    light_wins: f64,
+}
+
+impl Node {
+    fn new(game: GameState, mv: Option<Move>) -> Node {
+        Node { game, mv, children: Vec::new(), expanded: false, visits: 0, light_wins: 0.0 }
+    }
+
+    fn wins(&self, player: Player) -> f64 {
+        match player {
+            Player::Light => self.light_wins,
+            Player::Dark => self.visits as f64 - self.light_wins,
+        }
+    }
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    rng: SeededDice,
+}
+
+impl Tree {
+    // One selection, expansion, playout and backup from the root
+    fn iterate(&mut self, exploration: f64) {
+        let mut path = vec![0];
+        let mut current = 0;
+        loop {
+            if self.nodes[current].game.game_over() {
+                break;
+            }
+            if !self.nodes[current].expanded {
+                self.expand(current);
+                // Play out from a fresh child, or from the node itself before rolling
+                if let Some(child) = self.pick_child(current, exploration) {
+                    path.push(child);
+                    current = child;
+                }
+                break;
+            }
+            let Some(child) = self.pick_child(current, exploration) else {
+                break;
+            };
+            path.push(child);
+            current = child;
+        }
+
+        let start = self.nodes[current].game.detached();
+        let winner = playout(start, &mut self.rng);
+        for index in path {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            if winner == Player::Light {
+                node.light_wins += 1.0;
+            }
+        }
+    }
+
+    // Children: each legal move (or the pass) at a choice, each roll before rolling
+    fn expand(&mut self, index: usize) {
+        let game = &self.nodes[index].game;
+        let mut children = Vec::new();
+        if game.dice_value() == 0 {
+            for (value, _) in game.rules().dice.outcomes() {
+                let mut child = game.detached();
+                child.set_roll(value);
+                children.push(Node::new(child, None));
+            }
+        } else {
+            let moves = game.legal_moves();
+            if moves.is_empty() {
+                let mut child = game.detached();
+                child.pass_turn();
+                children.push(Node::new(child, None));
+            }
+            for mv in moves {
+                let mut child = game.detached();
+                child.apply_move(&mv);
+                children.push(Node::new(child, Some(mv)));
+            }
+        }
+
+        let first = self.nodes.len();
+        self.nodes.extend(children);
+        let last = self.nodes.len();
+        let node = &mut self.nodes[index];
+        node.children = (first..last).collect();
+        node.expanded = true;
+    }
+
+    fn pick_child(&mut self, index: usize, exploration: f64) -> Option<usize> {
+        let node = &self.nodes[index];
+        if node.children.is_empty() {
+            return None;
+        }
+        if node.game.dice_value() == 0 {
+            // Chance node: roll the dice, children being in outcome order
+            let dice = node.game.rules().dice;
+            let value = dice.sample(self.rng.next_u64());
+            let outcome = dice.outcomes().iter().position(|&(outcome, _)| outcome == value)?;
+            return Some(node.children[outcome]);
+        }
+
+        // UCT from the point of view of the player choosing, trying unvisited moves first
+        let player = node.game.current_player();
+        let parent_visits = (node.visits.max(1) as f64).ln();
+        node.children.iter().copied().max_by(|&a, &b| {
+            let score = |child: usize| {
+                let child = &self.nodes[child];
+                if child.visits == 0 {
+                    return f64::INFINITY;
+                }
+                let visits = child.visits as f64;
+                child.wins(player) / visits + exploration * (parent_visits / visits).sqrt()
+            };
+            score(a).total_cmp(&score(b))
+        })
+    }
+}
+
+// Plays uniformly random moves until someone wins
+pub(crate) fn playout(mut game: GameState, rng: &mut SeededDice) -> Player {
+    while !game.game_over() {
+        if game.dice_value() == 0 {
+            let roll = game.rules().dice.sample(rng.next_u64());
+            game.set_roll(roll);
+            continue;
+        }
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            game.pass_turn();
+            continue;
+        }
+        let mv = moves[(rng.next_u64() % moves.len() as u64) as usize];
+        game.apply_move(&mv);
+    }
+    game.winner().expect("finished games have a winner")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+
+    fn search(notation: &str, budget: Budget) -> Option<MctsResult> {
+        let game = GameState::from_notation(RuleSet::finkel(), notation).unwrap();
+        Mcts { seed: 7, ..Mcts::new(budget) }.search(&game)
+    }
+
+    #[test]
+    fn searches_pick_a_legal_move() {
+        let mut game = GameState::with_dice(RuleSet::finkel(), Box::new(SeededDice::new(3)));
+        let mut turns = 0;
+        while !game.game_over() && turns < 40 {
+            turns += 1;
+            game.roll_dice().unwrap();
+            let moves = game.legal_moves();
+            let Some(result) = Mcts { seed: turns, ..Mcts::new(Budget::Iterations(100)) }.search(&game) else {
+                assert!(moves.is_empty());
+                game.pass_turn();
+                continue;
+            };
+            assert!(moves.contains(&result.mv));
+            assert_eq!(result.moves.len(), moves.len());
+            assert_eq!(result.iterations, 100);
+            assert!(result.moves.iter().map(|stats| stats.visits).sum::<u32>() <= 100);
+            game.apply_move(&result.mv);
+        }
+    }
+
+    #[test]
+    fn searches_repeat_with_the_same_seed() {
+        let first = search("20 7/0 7/0 L 2", Budget::Iterations(300)).unwrap();
+        assert_eq!(search("20 7/0 7/0 L 2", Budget::Iterations(300)).unwrap(), first);
+    }
+
+    #[test]
+    fn nothing_is_searched_without_a_move() {
+        assert!(search("20 7/0 7/0 L -", Budget::Iterations(10)).is_none());
+        // Light's last piece can't land on Dark's piece on the central rosette
+        assert!(search("6LD12 0/6 6/0 L 1", Budget::Iterations(10)).is_none());
+        assert_eq!(search("20 7/0 7/0 L 3", Budget::Millis(0.0)).unwrap().iterations, 1);
+    }
+
+    #[test]
+    fn the_search_stops_a_piece_about_to_win() {
+        // Dark's last piece is three squares from home; Light's roll of 2 can capture it
+        let result = search("L12L3D2 0/5 0/6 L 2", Budget::Iterations(2000)).unwrap();
+        assert!(result.mv.captures, "{:?}", result.moves);
+    }
+}
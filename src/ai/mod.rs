@@ -0,0 +1,15 @@
+pub mod agent;
+pub mod analysis;
+pub mod estimate;
+pub mod eval;
+pub mod expectiminimax;
+pub mod mcts;
+pub mod solver;
+
+pub use agent::{agent_from_spec, play_game, Agent, GameResult, GreedyAgent, RandomAgent};
+pub use analysis::{Analysis, GameReport, MoveReview, PlayerSummary, Quality};
+pub use estimate::{Source, WinEstimate, WinEstimator};
+pub use eval::{Evaluator, WIN};
+pub use expectiminimax::{Expectiminimax, Pruning, SearchResult};
+pub use mcts::{Budget, Mcts, MctsResult, MoveStats};
+pub use solver::{Progress, SolveOptions, SolvedTable, SolverError};
@@ -0,0 +1,788 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Barrier;
+
+use crate::dice::splitmix64;
+use crate::game::{GameState, Player};
+use crate::moves::Move;
+use crate::rules::RuleSet;
+
+// Perfect play by value iteration over every position of a rule set.
+//
+// Positions are stored from the point of view of the player about to roll, which is enough
+// when the two routes mirror each other (true of every built-in variant): the mover's pieces
+// by their path positions and the opponent's by theirs. Each position's value is the chance
+// that the mover wins with perfect play on both sides.
+//
+// Bearing off is the only move that can't be undone by a capture, so positions are solved
+// in layers of pieces off, from the end of the game backwards. Each layer is iterated until
+// no value moves by more than the tolerance, with its positions shared out between threads.
+// Each sweep visits pieces further along their routes first, since most moves lead there.
+
+const MAGIC: &[u8; 8] = b"URSOLVE1";
+
+#[derive(Debug)]
+pub enum SolverError {
+    Asymmetric,     // The routes don't mirror each other, so positions can't be shared
+    NoPieces,       // The rules give each player no pieces, so there is no game to solve
+    TooLarge,       // Too many squares or positions for the table's indexing
+    Io(io::Error),
+    NotATable,      // The file doesn't start like a table
+    WrongRules,     // The table was solved for other rules
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::Asymmetric => write!(f, "the players' routes don't mirror each other"),
+            SolverError::NoPieces => write!(f, "the players have no pieces"),
+            SolverError::TooLarge => write!(f, "too many positions to index"),
+            SolverError::Io(error) => write!(f, "{}", error),
+            SolverError::NotATable => write!(f, "not a solved-game table"),
+            SolverError::WrongRules => write!(f, "the table was solved for different rules"),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+impl From<io::Error> for SolverError {
+    fn from(error: io::Error) -> Self {
+        SolverError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolveOptions {
+    pub threads: usize,
+    pub tolerance: f64,                  // Largest change in a sweep that counts as converged
+    pub progress: Option<fn(&Progress)>, // Called after each layer is solved
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        SolveOptions {
+            threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            tolerance: 1e-6,
+            progress: None,
+        }
+    }
+}
+
+// Reported as each layer of pieces off is finished
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub pieces_off: (u8, u8),   // Layer just solved, as pieces off of the two players
+    pub positions: usize,       // Positions in the layer
+    pub sweeps: u32,            // Sweeps it took to converge
+    pub solved: usize,          // Positions solved so far, out of total
+    pub total: usize,
+}
+
+// A solved rule set: the mover's chance of winning for every position before a roll
+pub struct SolvedTable {
+    rules: RuleSet,
+    index: StateIndex,
+    values: Vec<f32>,
+}
+
+impl SolvedTable {
+    pub fn solve(rules: RuleSet, options: &SolveOptions) -> Result<SolvedTable, SolverError> {
+        let index = StateIndex::new(&rules)?;
+        let values: Vec<AtomicU32> = (0..index.total).map(|_| AtomicU32::new(0.5f32.to_bits())).collect();
+        let mut solved = 0;
+
+        // Layers with more pieces off come first, as bearing off leads into them
+        let last = rules.pieces - 1;
+        for sum in (0..=2 * last).rev() {
+            for a in sum.saturating_sub(last)..=sum / 2 {
+                let b = sum - a;
+                // A layer and its mirror image turn into each other whenever the turn passes
+                let mut layer = vec![(a, b)];
+                if a != b {
+                    layer.push((b, a));
+                }
+                let sweeps = index.solve_layer(&values, &layer, options);
+                let positions: usize = layer.iter().map(|&(m, o)| index.layer_len(m, o)).sum();
+                solved += positions;
+                if let Some(progress) = options.progress {
+                    progress(&Progress { pieces_off: (a, b), positions, sweeps, solved, total: index.total });
+                }
+            }
+        }
+
+        let values = values.into_iter().map(|value| f32::from_bits(value.into_inner())).collect();
+        Ok(SolvedTable { rules, index, values })
+    }
+
+    // Number of positions a table for these rules holds, each taking 4 bytes
+    pub fn positions(rules: &RuleSet) -> Result<usize, SolverError> {
+        StateIndex::new(rules).map(|index| index.total)
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    // Number of positions in the table
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // Chance that the player to move wins with perfect play, before or after their roll.
+    // None if the game isn't played under the table's rules
+    pub fn win_probability(&self, game: &GameState) -> Option<f64> {
+        if game.rules() != &self.rules {
+            return None;
+        }
+        if let Some(winner) = game.winner() {
+            return Some(if winner == game.current_player() { 1.0 } else { 0.0 });
+        }
+        if game.dice_value() == 0 {
+            return Some(self.lookup(&Position::from_game(game)));
+        }
+        match self.best(game) {
+            Some((_, value)) => Some(value),
+            // Nothing can move, so the turn passes
+            None => Some(1.0 - self.lookup(&Position::from_game(game).swapped())),
+        }
+    }
+
+    // The move with the best chance of winning for the current roll, or None before
+    // rolling, when nothing can move or under other rules
+    pub fn best_move(&self, game: &GameState) -> Option<Move> {
+        if game.rules() != &self.rules || game.game_over() || game.dice_value() == 0 {
+            return None;
+        }
+        self.best(game).map(|(mv, _)| mv)
+    }
+
+    // Each legal move with the mover's chance of winning after it
+    pub fn move_values(&self, game: &GameState) -> Vec<(Move, f64)> {
+        if game.rules() != &self.rules || game.game_over() || game.dice_value() == 0 {
+            return Vec::new();
+        }
+        let player = game.current_player();
+        game.legal_moves().into_iter().map(|mv| {
+            let mut child = game.detached();
+            child.apply_move(&mv);
+            let value = match child.winner() {
+                Some(winner) => if winner == player { 1.0 } else { 0.0 },
+                None if child.current_player() == player => self.lookup(&Position::from_game(&child)),
+                None => 1.0 - self.lookup(&Position::from_game(&child)),
+            };
+            (mv, value)
+        }).collect()
+    }
+
+    fn best(&self, game: &GameState) -> Option<(Move, f64)> {
+        self.move_values(game).into_iter().max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    fn lookup(&self, position: &Position) -> f64 {
+        self.values[self.index.index(position)] as f64
+    }
+
+    // Header (magic, rules fingerprint, position count) followed by each value as a
+    // little-endian f32, in index order
+    pub fn write_to(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&fingerprint(&self.rules).to_le_bytes())?;
+        writer.write_all(&(self.values.len() as u64).to_le_bytes())?;
+        for value in &self.values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from(rules: RuleSet, reader: impl Read) -> Result<SolvedTable, SolverError> {
+        let index = StateIndex::new(&rules)?;
+        let mut reader = BufReader::new(reader);
+        let mut header = [0u8; 24];
+        reader.read_exact(&mut header).map_err(|_| SolverError::NotATable)?;
+        if &header[..8] != MAGIC {
+            return Err(SolverError::NotATable);
+        }
+        let stored_fingerprint = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let count = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        if stored_fingerprint != fingerprint(&rules) || count != index.total as u64 {
+            return Err(SolverError::WrongRules);
+        }
+
+        let mut values = Vec::with_capacity(index.total);
+        let mut bytes = [0u8; 4];
+        for _ in 0..index.total {
+            reader.read_exact(&mut bytes)?;
+            values.push(f32::from_le_bytes(bytes));
+        }
+        Ok(SolvedTable { rules, index, values })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_to(File::create(path)?)
+    }
+
+    pub fn load(rules: RuleSet, path: impl AsRef<Path>) -> Result<SolvedTable, SolverError> {
+        SolvedTable::read_from(rules, File::open(path)?)
+    }
+}
+
+impl fmt::Debug for SolvedTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SolvedTable").field("rules", &self.rules).field("positions", &self.values.len()).finish()
+    }
+}
+
+// Identifies the rules a table was solved for
+fn fingerprint(rules: &RuleSet) -> u64 {
+    let mut words = vec![
+        rules.pieces as u64,
+        rules.safe_rosettes as u64,
+        rules.rosettes_grant_extra_roll as u64,
+        rules.captures_grant_extra_roll as u64,
+        rules.exact_bear_off as u64,
+    ];
+    words.extend(rules.dice.weights().iter().map(|&weight| weight as u64));
+    for player in [Player::Light, Player::Dark] {
+        words.push(u64::MAX);
+        for &square in rules.route(player).squares() {
+            words.push(square as u64 * 2 + rules.layout().is_rosette(square) as u64);
+        }
+    }
+    words.into_iter().fold(0, |hash, word| splitmix64(hash ^ word).wrapping_add(word))
+}
+
+// A position before the roll, with path positions as bits (bit p for path position p)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    mover: u32,
+    opponent: u32,
+    mover_off: u8,
+    opponent_off: u8,
+}
+
+impl Position {
+    fn from_game(game: &GameState) -> Position {
+        let mover = game.current_player();
+        let opponent = mover.opponent();
+        let bits = |player: Player| {
+            game.rules().route(player).squares().iter().enumerate()
+                .filter(|&(_, &square)| game.board()[square].piece == Some(player))
+                .fold(0u32, |bits, (i, _)| bits | 1 << (i + 1))
+        };
+        Position {
+            mover: bits(mover),
+            opponent: bits(opponent),
+            mover_off: game.pieces_off(mover),
+            opponent_off: game.pieces_off(opponent),
+        }
+    }
+
+    // The same position with the other player to move
+    fn swapped(&self) -> Position {
+        Position {
+            mover: self.opponent,
+            opponent: self.mover,
+            mover_off: self.opponent_off,
+            opponent_off: self.mover_off,
+        }
+    }
+}
+
+// Dense numbering of every position where neither player has won. Positions are grouped
+// by pieces off, then by the shared squares (empty, mover or opponent), then by the
+// squares only one player visits, counting only piece placements that fit in the start
+// area's supply
+struct StateIndex {
+    pieces: u8,
+    route_len: usize,
+    rosettes: u32,                    // Path positions that are rosettes
+    opponent_position: Vec<u8>,       // Opponent's path position of each of the mover's, 0 if private
+    shared: Vec<(u8, u8)>,            // Mover and opponent path positions of each shared square
+    private: Vec<u8>,                 // Path positions only their owner visits
+    mask_rank: Vec<Vec<u32>>,         // [free pieces][private mask], ranked among masks that fit
+    mask_unrank: Vec<Vec<u32>>,       // [free pieces][rank]
+    layer_offsets: Vec<usize>,        // [mover off * pieces + opponent off]
+    shared_offsets: Vec<Vec<u32>>,    // [layer][shared configuration], within the layer
+    sweep_order: Vec<u32>,            // Shared configurations, furthest along first
+    layer_lens: Vec<usize>,
+    total: usize,
+    outcomes: Vec<(u8, f64)>,
+    safe_rosettes: bool,
+    rosettes_grant_extra_roll: bool,
+    captures_grant_extra_roll: bool,
+    exact_bear_off: bool,
+}
+
+impl StateIndex {
+    fn new(rules: &RuleSet) -> Result<StateIndex, SolverError> {
+        if rules.pieces == 0 {
+            return Err(SolverError::NoPieces);
+        }
+        let light = rules.route(Player::Light).squares();
+        let dark = rules.route(Player::Dark).squares();
+        if light.len() != dark.len() || light.len() > 31 {
+            return Err(if light.len() != dark.len() { SolverError::Asymmetric } else { SolverError::TooLarge });
+        }
+        let route_len = light.len();
+
+        // Mirror check: path positions i and j share a square exactly when j and i do,
+        // and rosettes sit at the same path positions on both routes
+        let mut opponent_position = vec![0u8; route_len + 1];
+        let mut rosettes = 0u32;
+        for i in 1..=route_len {
+            let light_square = light[i - 1];
+            if rules.layout().is_rosette(light_square) != rules.layout().is_rosette(dark[i - 1]) {
+                return Err(SolverError::Asymmetric);
+            }
+            if rules.layout().is_rosette(light_square) {
+                rosettes |= 1 << i;
+            }
+            if let Some(j) = dark.iter().position(|&square| square == light_square) {
+                if light.iter().position(|&square| square == dark[i - 1]) != Some(j) {
+                    return Err(SolverError::Asymmetric);
+                }
+                opponent_position[i] = (j + 1) as u8;
+            }
+        }
+        let shared: Vec<(u8, u8)> = (1..=route_len)
+            .filter(|&i| opponent_position[i] != 0)
+            .map(|i| (i as u8, opponent_position[i]))
+            .collect();
+        let private: Vec<u8> = (1..=route_len).filter(|&i| opponent_position[i] == 0).map(|i| i as u8).collect();
+        if private.len() > 16 || shared.len() > 16 {
+            return Err(SolverError::TooLarge);
+        }
+
+        let pieces = rules.pieces;
+        let mut mask_rank = Vec::new();
+        let mut mask_unrank = Vec::new();
+        for free in 0..=pieces as u32 {
+            let mut rank = vec![u32::MAX; 1 << private.len()];
+            let mut unrank = Vec::new();
+            for mask in 0..1u32 << private.len() {
+                if mask.count_ones() <= free {
+                    rank[mask as usize] = unrank.len() as u32;
+                    unrank.push(mask);
+                }
+            }
+            mask_rank.push(rank);
+            mask_unrank.push(unrank);
+        }
+
+        let configurations = 3usize.pow(shared.len() as u32);
+        let mut progress = vec![0u32; configurations];
+        let counts: Vec<(u8, u8)> = (0..configurations).map(|config| {
+            let mut rest = config;
+            let (mut mover, mut opponent) = (0, 0);
+            for &(mover_pos, opponent_pos) in &shared {
+                match rest % 3 {
+                    1 => {
+                        mover += 1;
+                        progress[config] += mover_pos as u32;
+                    }
+                    2 => {
+                        opponent += 1;
+                        progress[config] += opponent_pos as u32;
+                    }
+                    _ => {}
+                }
+                rest /= 3;
+            }
+            (mover, opponent)
+        }).collect();
+        let mut sweep_order: Vec<u32> = (0..configurations as u32).collect();
+        sweep_order.sort_by_key(|&config| std::cmp::Reverse(progress[config as usize]));
+
+        let mut layer_offsets = Vec::new();
+        let mut shared_offsets = Vec::new();
+        let mut layer_lens = Vec::new();
+        let mut total = 0usize;
+        for mover_off in 0..pieces {
+            for opponent_off in 0..pieces {
+                let mut offsets = Vec::with_capacity(configurations);
+                let mut len = 0usize;
+                for &(mover, opponent) in &counts {
+                    offsets.push(u32::try_from(len).map_err(|_| SolverError::TooLarge)?);
+                    let mover_free = (pieces - mover_off) as i32 - mover as i32;
+                    let opponent_free = (pieces - opponent_off) as i32 - opponent as i32;
+                    if mover_free >= 0 && opponent_free >= 0 {
+                        len += mask_unrank[mover_free as usize].len() * mask_unrank[opponent_free as usize].len();
+                    }
+                }
+                layer_offsets.push(total);
+                shared_offsets.push(offsets);
+                layer_lens.push(len);
+                total = total.checked_add(len).ok_or(SolverError::TooLarge)?;
+            }
+        }
+
+        Ok(StateIndex {
+            pieces,
+            route_len,
+            rosettes,
+            opponent_position,
+            shared,
+            private,
+            mask_rank,
+            mask_unrank,
+            layer_offsets,
+            shared_offsets,
+            sweep_order,
+            layer_lens,
+            total,
+            outcomes: rules.dice.outcomes(),
+            safe_rosettes: rules.safe_rosettes,
+            rosettes_grant_extra_roll: rules.rosettes_grant_extra_roll,
+            captures_grant_extra_roll: rules.captures_grant_extra_roll,
+            exact_bear_off: rules.exact_bear_off,
+        })
+    }
+
+    fn layer(&self, mover_off: u8, opponent_off: u8) -> usize {
+        mover_off as usize * self.pieces as usize + opponent_off as usize
+    }
+
+    fn layer_len(&self, mover_off: u8, opponent_off: u8) -> usize {
+        self.layer_lens[self.layer(mover_off, opponent_off)]
+    }
+
+    // Whether any position of the layer has the shared configuration
+    fn has_positions(&self, mover_off: u8, opponent_off: u8, config: usize) -> bool {
+        let layer = self.layer(mover_off, opponent_off);
+        let end = self.shared_offsets[layer].get(config + 1).map_or(self.layer_lens[layer], |&end| end as usize);
+        end > self.shared_offsets[layer][config] as usize
+    }
+
+    fn index(&self, position: &Position) -> usize {
+        let mut config = 0usize;
+        let (mut mover_shared, mut opponent_shared) = (0u8, 0u8);
+        for &(mover_pos, opponent_pos) in self.shared.iter().rev() {
+            config *= 3;
+            if position.mover & 1 << mover_pos != 0 {
+                config += 1;
+                mover_shared += 1;
+            } else if position.opponent & 1 << opponent_pos != 0 {
+                config += 2;
+                opponent_shared += 1;
+            }
+        }
+        let (mut mover_mask, mut opponent_mask) = (0usize, 0usize);
+        for (bit, &pos) in self.private.iter().enumerate() {
+            mover_mask |= ((position.mover >> pos & 1) as usize) << bit;
+            opponent_mask |= ((position.opponent >> pos & 1) as usize) << bit;
+        }
+
+        let mover_free = (self.pieces - position.mover_off - mover_shared) as usize;
+        let opponent_free = (self.pieces - position.opponent_off - opponent_shared) as usize;
+        let layer = self.layer(position.mover_off, position.opponent_off);
+        self.layer_offsets[layer]
+            + self.shared_offsets[layer][config] as usize
+            + self.mask_rank[mover_free][mover_mask] as usize * self.mask_unrank[opponent_free].len()
+            + self.mask_rank[opponent_free][opponent_mask] as usize
+    }
+
+    // Every position of one layer with a given shared configuration, with pieces on the
+    // private squares furthest along first
+    fn positions(&self, mover_off: u8, opponent_off: u8, config: usize, mut visit: impl FnMut(Position)) {
+        let mut rest = config;
+        let (mut mover, mut opponent) = (0u32, 0u32);
+        for &(mover_pos, opponent_pos) in &self.shared {
+            match rest % 3 {
+                1 => mover |= 1 << mover_pos,
+                2 => opponent |= 1 << opponent_pos,
+                _ => {}
+            }
+            rest /= 3;
+        }
+        let mover_free = self.pieces as i32 - mover_off as i32 - mover.count_ones() as i32;
+        let opponent_free = self.pieces as i32 - opponent_off as i32 - opponent.count_ones() as i32;
+        if mover_free < 0 || opponent_free < 0 {
+            return;
+        }
+
+        let spread = |mask: u32| {
+            self.private.iter().enumerate().fold(0u32, |bits, (bit, &pos)| bits | (mask >> bit & 1) << pos)
+        };
+        for &mover_mask in self.mask_unrank[mover_free as usize].iter().rev() {
+            for &opponent_mask in self.mask_unrank[opponent_free as usize].iter().rev() {
+                visit(Position {
+                    mover: mover | spread(mover_mask),
+                    opponent: opponent | spread(opponent_mask),
+                    mover_off,
+                    opponent_off,
+                });
+            }
+        }
+    }
+
+    // Sweeps the layers until converged, returning the number of sweeps. The threads are
+    // started once and meet at a barrier after each sweep, where one of them decides
+    // whether another is needed
+    fn solve_layer(&self, values: &[AtomicU32], layer: &[(u8, u8)], options: &SolveOptions) -> u32 {
+        // Most configurations hold more pieces than the layer has when pieces are few
+        let items: Vec<(u8, u8, usize)> = layer.iter()
+            .flat_map(|&(mover_off, opponent_off)| {
+                self.sweep_order.iter().map(move |&config| (mover_off, opponent_off, config as usize))
+            })
+            .filter(|&(mover_off, opponent_off, config)| self.has_positions(mover_off, opponent_off, config))
+            .collect();
+        let work = items.len();
+        let threads = options.threads.max(1);
+        let next = AtomicUsize::new(0);
+        let change = AtomicU64::new(0);
+        let sweeps = AtomicU32::new(0);
+        let done = AtomicBool::new(false);
+        let barrier = Barrier::new(threads);
+
+        // Each worker takes batches of shared configurations until none are left
+        let worker = || loop {
+            let mut max_change = 0.0f64;
+            loop {
+                let start = next.fetch_add(64, Ordering::Relaxed);
+                if start >= work {
+                    break;
+                }
+                for &(mover_off, opponent_off, config) in &items[start..(start + 64).min(work)] {
+                    self.positions(mover_off, opponent_off, config, |position| {
+                        let slot = &values[self.index(&position)];
+                        let value = self.value(values, &position);
+                        let old = f32::from_bits(slot.load(Ordering::Relaxed)) as f64;
+                        max_change = max_change.max((value - old).abs());
+                        slot.store((value as f32).to_bits(), Ordering::Relaxed);
+                    });
+                }
+            }
+            // Non-negative floats order the same way as their bits
+            change.fetch_max(max_change.to_bits(), Ordering::Relaxed);
+
+            if barrier.wait().is_leader() {
+                sweeps.fetch_add(1, Ordering::Relaxed);
+                let converged = f64::from_bits(change.swap(0, Ordering::Relaxed)) <= options.tolerance;
+                done.store(converged, Ordering::Relaxed);
+                next.store(0, Ordering::Relaxed);
+            }
+            barrier.wait();
+            if done.load(Ordering::Relaxed) {
+                break;
+            }
+        };
+
+        std::thread::scope(|scope| {
+            let helpers: Vec<_> = (1..threads).map(|_| scope.spawn(worker)).collect();
+            worker();
+            for helper in helpers {
+                helper.join().expect("solver thread panicked");
+            }
+        });
+        sweeps.into_inner()
+    }
+
+    // Bellman update: the chance of the mover winning, averaged over rolls, taking the
+    // best move for each
+    fn value(&self, values: &[AtomicU32], position: &Position) -> f64 {
+        let stored = |position: &Position| f32::from_bits(values[self.index(position)].load(Ordering::Relaxed)) as f64;
+        let pass = 1.0 - stored(&position.swapped());
+
+        let mut expected = 0.0;
+        for &(roll, probability) in &self.outcomes {
+            if roll == 0 {
+                expected += probability * pass;
+                continue;
+            }
+            let mut best: Option<f64> = None;
+            self.moves(position, roll, |child, extra_turn| {
+                let value = if child.mover_off == self.pieces {
+                    1.0
+                } else if extra_turn {
+                    stored(&child)
+                } else {
+                    1.0 - stored(&child.swapped())
+                };
+                best = Some(best.map_or(value, |best: f64| best.max(value)));
+            });
+            expected += probability * best.unwrap_or(pass);
+        }
+        expected
+    }
+
+    // Each position the mover can reach with the roll, and whether they roll again.
+    // Mirrors GameState::check_move and apply_move
+    fn moves(&self, position: &Position, roll: u8, mut visit: impl FnMut(Position, bool)) {
+        let roll = roll as usize;
+        let off = self.route_len + 1;
+        let on_board = position.mover.count_ones() as u8;
+        let in_start = self.pieces - position.mover_off - on_board;
+
+        for from in 0..=self.route_len {
+            let from_start = from == 0;
+            if from_start && in_start == 0 || !from_start && position.mover & 1 << from == 0 {
+                continue;
+            }
+            let to = from + roll;
+            let mut child = *position;
+            child.mover &= !(1 << from);
+
+            if to >= off {
+                if from_start || to != off && self.exact_bear_off {
+                    continue;
+                }
+                child.mover_off += 1;
+                visit(child, false);
+                continue;
+            }
+
+            if position.mover & 1 << to != 0 {
+                continue;
+            }
+            let rosette = self.rosettes & 1 << to != 0;
+            let opponent_pos = self.opponent_position[to];
+            let captures = opponent_pos != 0 && position.opponent & 1 << opponent_pos != 0;
+            if captures && rosette && self.safe_rosettes {
+                continue;
+            }
+            if captures {
+                child.opponent &= !(1 << opponent_pos);
+            }
+            child.mover |= 1 << to;
+            let extra_turn = rosette && self.rosettes_grant_extra_roll || captures && self.captures_grant_extra_roll;
+            visit(child, extra_turn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Variant;
+
+    fn with_pieces(variant: Variant, pieces: u8) -> RuleSet {
+        let mut rules = RuleSet::for_variant(variant);
+        rules.pieces = pieces;
+        rules
+    }
+
+    fn small_rules() -> impl Iterator<Item = RuleSet> {
+        Variant::ALL.into_iter().flat_map(|variant| (1..=2).map(move |pieces| with_pieces(variant, pieces)))
+    }
+
+    // Every position of the table, each visited once
+    fn all_positions(index: &StateIndex) -> Vec<Position> {
+        let mut positions = Vec::new();
+        for mover_off in 0..index.pieces {
+            for opponent_off in 0..index.pieces {
+                for config in 0..index.sweep_order.len() {
+                    index.positions(mover_off, opponent_off, config, |position| positions.push(position));
+                }
+            }
+        }
+        positions
+    }
+
+    // The position as a game with Light to move, before the roll
+    fn game_at(rules: &RuleSet, position: &Position) -> GameState {
+        let mut pieces = vec![None; rules.layout().len()];
+        for (player, bits) in [(Player::Light, position.mover), (Player::Dark, position.opponent)] {
+            for (i, &square) in rules.route(player).squares().iter().enumerate() {
+                if bits & 1 << (i + 1) != 0 {
+                    pieces[square] = Some(player);
+                }
+            }
+        }
+        GameState::from_parts(rules.clone(), &pieces, position.mover_off, position.opponent_off, Player::Light, 0)
+    }
+
+    #[test]
+    fn rules_without_pieces_are_rejected() {
+        let rules = with_pieces(Variant::Finkel, 0);
+        assert!(matches!(SolvedTable::positions(&rules), Err(SolverError::NoPieces)));
+        assert!(matches!(SolvedTable::solve(rules, &SolveOptions::default()), Err(SolverError::NoPieces)));
+    }
+
+    #[test]
+    fn positions_are_numbered_densely() {
+        for rules in small_rules() {
+            let index = StateIndex::new(&rules).unwrap();
+            let mut seen = vec![false; index.total];
+            for position in all_positions(&index) {
+                let i = index.index(&position);
+                assert!(!seen[i], "{:?} {:?} numbered twice", rules.variant, position);
+                seen[i] = true;
+            }
+            assert!(seen.iter().all(|&seen| seen), "{:?} leaves gaps", rules.variant);
+        }
+    }
+
+    #[test]
+    fn solver_moves_match_the_game() {
+        for rules in small_rules() {
+            let index = StateIndex::new(&rules).unwrap();
+            for position in all_positions(&index) {
+                let game = game_at(&rules, &position);
+                assert_eq!(Position::from_game(&game), position);
+                for roll in 1..=rules.dice.max_value() {
+                    let mut expected = Vec::new();
+                    let mut rolled = game.detached();
+                    rolled.set_roll(roll);
+                    for mv in rolled.legal_moves() {
+                        let mut child = rolled.detached();
+                        let outcome = child.apply_move(&mv);
+                        let reached = Position::from_game(&child);
+                        let reached = if child.current_player() == Player::Light { reached } else { reached.swapped() };
+                        expected.push((reached.mover, reached.opponent, reached.mover_off, outcome.extra_turn));
+                    }
+                    let mut found = Vec::new();
+                    index.moves(&position, roll, |child, extra_turn| {
+                        found.push((child.mover, child.opponent, child.mover_off, extra_turn));
+                    });
+                    expected.sort_unstable();
+                    found.sort_unstable();
+                    assert_eq!(found, expected, "{:?} {:?} roll {}", rules.variant, position, roll);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solved_values_satisfy_the_bellman_equation() {
+        // One piece each keeps the solves quick in debug builds
+        let options = SolveOptions { threads: 2, ..SolveOptions::default() };
+        for rules in Variant::ALL.map(|variant| with_pieces(variant, 1)) {
+            let table = SolvedTable::solve(rules.clone(), &options).unwrap();
+            for position in all_positions(&table.index) {
+                // Averaging the best move's value over every roll, by the game's own move
+                // generation, gives back the value stored before the roll
+                let game = game_at(&rules, &position);
+                let stored = table.win_probability(&game).unwrap();
+                assert!((0.0..=1.0).contains(&stored));
+                let expected: f64 = rules.dice.outcomes().into_iter().map(|(roll, probability)| {
+                    let mut rolled = game.detached();
+                    rolled.set_roll(roll);
+                    let value = table.win_probability(&rolled).unwrap();
+                    probability * if rolled.current_player() == Player::Light { value } else { 1.0 - value }
+                }).sum();
+                assert!((stored - expected).abs() < 1e-4, "{:?} {:?}: {} vs {}", rules.variant, position, stored, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn a_single_piece_race_goes_to_the_player_in_front() {
+        let rules = with_pieces(Variant::Finkel, 1);
+        let table = SolvedTable::solve(rules.clone(), &SolveOptions::default()).unwrap();
+        // Light is one square from bearing off, Dark hasn't entered
+        let ahead = Position { mover: 1 << 14, opponent: 0, mover_off: 0, opponent_off: 0 };
+        let behind = ahead.swapped();
+        let value = table.win_probability(&game_at(&rules, &ahead)).unwrap();
+        assert!(value > 0.8, "{}", value);
+        assert!(table.win_probability(&game_at(&rules, &behind)).unwrap() < 0.5);
+    }
+}
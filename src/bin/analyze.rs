@@ -0,0 +1,93 @@
+// Reviews a finished game record, labelling each move best, inaccuracy, mistake or blunder.
+// Prints the record annotated with comments, or the full report as JSON.
+//
+//   cargo run --profile native --bin analyze -- [options] <record file>
+//
+//   --table <file>       Solved table from the solve tool, for exact win probabilities
+//   --pieces <n>         Pieces per player the table was solved for
+//   --depth <n>          Search depth without a table (2)
+//   --rollouts <n>       Use this many random playouts instead of search
+//   --json               Print the report as JSON
+
+use std::fs;
+use std::process;
+
+use game_of_ur::ai::{Analysis, Expectiminimax, SolvedTable, WinEstimator};
+use game_of_ur::{GameRecord, Player, RuleSet};
+
+fn main() {
+    let mut table = None;
+    let mut pieces = None;
+    let mut estimator = WinEstimator::new();
+    let mut json = false;
+    let mut input = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+        match arg.as_str() {
+            "--table" => table = Some(value("--table")),
+            "--pieces" => pieces = Some(parse(&value("--pieces"), "--pieces")),
+            "--depth" => estimator.search = Some(Expectiminimax::new(parse(&value("--depth"), "--depth"))),
+            "--rollouts" => {
+                estimator.rollouts = parse(&value("--rollouts"), "--rollouts");
+                estimator.search = None;
+            }
+            "--json" => json = true,
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+            _ => input = Some(arg),
+        }
+    }
+    let input = input.unwrap_or_else(|| usage());
+
+    let text = fs::read_to_string(&input).unwrap_or_else(|error| fail(&format!("can't read {}: {}", input, error)));
+    let record: GameRecord = text.parse().unwrap_or_else(|error: game_of_ur::RecordError| fail(&error.to_string()));
+    let mut rules = RuleSet::for_variant(record.variant);
+    if let Some(pieces) = pieces {
+        if !(1..=u32::from(rules.max_pieces())).contains(&pieces) {
+            fail(&format!("--pieces must be between 1 and {}", rules.max_pieces()));
+        }
+        rules.pieces = pieces as u8;
+    }
+    if let Some(path) = table {
+        let loaded = SolvedTable::load(rules.clone(), &path)
+            .unwrap_or_else(|error| fail(&format!("can't load {}: {}", path, error)));
+        estimator.table = Some(loaded);
+    }
+
+    let analysis = Analysis::new(estimator);
+    let report = analysis.analyze_with(&record, rules).unwrap_or_else(|error| fail(&error.to_string()));
+    if json {
+        println!("{}", report.to_json());
+        return;
+    }
+
+    print!("{}", report.annotate(&record));
+    for player in [Player::Light, Player::Dark] {
+        let summary = report.summary(player);
+        eprintln!(
+            "{:?}: {} moves, {} inaccuracies, {} mistakes, {} blunders, {:.1}% lost per move",
+            player,
+            summary.moves,
+            summary.inaccuracies,
+            summary.mistakes,
+            summary.blunders,
+            100.0 * summary.average_loss,
+        );
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> T {
+    value.parse().unwrap_or_else(|_| fail(&format!("invalid value '{}' for {}", value, name)))
+}
+
+fn usage() -> ! {
+    eprintln!("usage: analyze [--table <file>] [--pieces <n>] [--depth <n>] [--rollouts <n>] [--json] <record file>");
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("analyze: {}", message);
+    process::exit(1);
+}
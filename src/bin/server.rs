@@ -0,0 +1,136 @@
+// Hosts games over WebSocket so two people can play from different browsers, and others
+// can watch. The message schema is described in room.rs.
+//
+//   cargo run --features server --bin server -- [--address <host:port>]
+//
+//   --address <host:port>   Where to listen (127.0.0.1:9001)
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use game_of_ur::room::{ClientId, ClientMessage, Lobby, Outgoing, ServerMessage};
+use tungstenite::error::ProtocolError;
+use tungstenite::{Message, WebSocket};
+
+// How often each connection checks for messages to push while waiting for its client
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How often timed games are checked for a player who has run out of time
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+fn main() {
+    let mut address = "127.0.0.1:9001".to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--address" => address = args.next().unwrap_or_else(|| fail("--address needs a value")),
+            "-h" | "--help" => usage(),
+            _ => fail(&format!("unknown option '{}'", arg)),
+        }
+    }
+
+    let listener = TcpListener::bind(&address).unwrap_or_else(|error| fail(&format!("can't listen on {}: {}", address, error)));
+    eprintln!("Listening on ws://{}", address);
+    let server = Arc::new(Server::default());
+    let ticker = Arc::clone(&server);
+    thread::spawn(move || loop {
+        thread::sleep(TICK_INTERVAL);
+        let outgoing = ticker.lobby.lock().unwrap().tick();
+        ticker.deliver(outgoing);
+    });
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            if let Err(error) = server.serve(stream) {
+                eprintln!("connection closed: {}", error);
+            }
+        });
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    lobby: Mutex<Lobby>,
+    clients: Mutex<HashMap<ClientId, Sender<(Instant, String)>>>,  // Queues of JSON messages to push, and when
+    next_id: AtomicU64,
+}
+
+impl Server {
+    // Runs one connection until the client goes away, then frees its seat
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        let mut socket = tungstenite::accept(stream).map_err(|error| io::Error::other(error.to_string()))?;
+        socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let client = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        self.clients.lock().unwrap().insert(client, sender);
+        let result = self.run(client, &mut socket, &receiver);
+
+        self.clients.lock().unwrap().remove(&client);
+        let outgoing = self.lobby.lock().unwrap().leave(client);
+        self.deliver(outgoing);
+        result
+    }
+
+    fn run(&self, client: ClientId, socket: &mut WebSocket<TcpStream>, pending: &mpsc::Receiver<(Instant, String)>) -> io::Result<()> {
+        let mut waiting = Vec::new();
+        loop {
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    let outgoing = match serde_json::from_str::<ClientMessage>(&text) {
+                        Ok(message) => self.lobby.lock().unwrap().handle(client, message),
+                        Err(error) => vec![Outgoing::now(
+                            client,
+                            ServerMessage::Error { message: format!("Unreadable message: {}", error) },
+                        )],
+                    };
+                    self.deliver(outgoing);
+                }
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(error))
+                    if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(tungstenite::Error::ConnectionClosed)
+                | Err(tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => return Ok(()),
+                Err(error) => return Err(io::Error::other(error)),
+            }
+            // Spectators' messages wait out their delay, in the order they were queued
+            waiting.extend(pending.try_iter());
+            let now = Instant::now();
+            while let Some(index) = waiting.iter().position(|(due, _)| *due <= now) {
+                let (_, text) = waiting.remove(index);
+                socket.send(Message::Text(text)).map_err(io::Error::other)?;
+            }
+        }
+    }
+
+    // Queues each message on its client's connection
+    fn deliver(&self, outgoing: Vec<Outgoing>) {
+        let clients = self.clients.lock().unwrap();
+        let now = Instant::now();
+        for Outgoing { to, message, delay } in outgoing {
+            if let Some(sender) = clients.get(&to) {
+                let _ = sender.send((now + delay, serde_json::to_string(&message).expect("messages serialize")));
+            }
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: server [--address <host:port>]");
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("server: {}", message);
+    process::exit(1);
+}
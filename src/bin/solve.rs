@@ -0,0 +1,86 @@
+// Solves a rule set by value iteration and writes the table for SolvedTable::load.
+//
+//   cargo run --profile native --bin solve -- [options] <output file>
+//
+//   --variant <name>     Finkel (default), Masters, Blitz or Aseb
+//   --pieces <n>         Pieces per player, to solve a smaller game
+//   --threads <n>        Worker threads, all cores by default
+//   --tolerance <x>      Largest change in a sweep that counts as converged (1e-6)
+
+use std::process;
+
+use game_of_ur::ai::{Progress, SolveOptions, SolvedTable};
+use game_of_ur::{GameState, RuleSet, Variant};
+
+fn main() {
+    let mut variant = Variant::Finkel;
+    let mut pieces = None;
+    let mut options = SolveOptions { progress: Some(report), ..SolveOptions::default() };
+    let mut output = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+        match arg.as_str() {
+            "--variant" => {
+                let name = value("--variant");
+                variant = Variant::from_name(&name).unwrap_or_else(|| fail(&format!("unknown variant '{}'", name)));
+            }
+            "--pieces" => pieces = Some(parse(&value("--pieces"), "--pieces")),
+            "--threads" => options.threads = parse(&value("--threads"), "--threads"),
+            "--tolerance" => options.tolerance = parse(&value("--tolerance"), "--tolerance"),
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+            _ => output = Some(arg),
+        }
+    }
+    let output = output.unwrap_or_else(|| usage());
+    let mut rules = RuleSet::for_variant(variant);
+    if let Some(pieces) = pieces {
+        if !(1..=u32::from(rules.max_pieces())).contains(&pieces) {
+            fail(&format!("--pieces must be between 1 and {}", rules.max_pieces()));
+        }
+        rules.pieces = pieces as u8;
+    }
+
+    let positions = SolvedTable::positions(&rules).unwrap_or_else(|error| fail(&error.to_string()));
+    eprintln!(
+        "Solving {} with {} pieces: {} positions, {} MB, {} threads",
+        rules.variant.name(),
+        rules.pieces,
+        positions,
+        positions * 4 / 1_000_000,
+        options.threads,
+    );
+    let table = SolvedTable::solve(rules, &options).unwrap_or_else(|error| fail(&error.to_string()));
+
+    let start = GameState::new(table.rules().clone());
+    eprintln!("Light wins {:.4}% of games with perfect play", 100.0 * table.win_probability(&start).unwrap_or(0.0));
+    table.save(&output).unwrap_or_else(|error| fail(&format!("can't write {}: {}", output, error)));
+    eprintln!("Wrote {}", output);
+}
+
+fn report(progress: &Progress) {
+    eprintln!(
+        "  pieces off {}/{}: {} positions in {} sweeps ({:.1}% done)",
+        progress.pieces_off.0,
+        progress.pieces_off.1,
+        progress.positions,
+        progress.sweeps,
+        100.0 * progress.solved as f64 / progress.total.max(1) as f64,
+    );
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> T {
+    value.parse().unwrap_or_else(|_| fail(&format!("invalid value '{}' for {}", value, name)))
+}
+
+fn usage() -> ! {
+    eprintln!("usage: solve [--variant <name>] [--pieces <n>] [--threads <n>] [--tolerance <x>] <output file>");
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("solve: {}", message);
+    process::exit(1);
+}
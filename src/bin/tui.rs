@@ -0,0 +1,335 @@
+// Plays Ur in the terminal, against another person or a computer player.
+//
+//   cargo run --features tui --bin tui -- [options]
+//
+//   --variant <name>     Finkel (default), Masters, Blitz or Aseb
+//   --seed <n>           Seed the dice so a game can be replayed
+//   --computer <player>  Let an engine play: random, greedy, expectiminimax:<depth>,
+//                        mcts:<iterations> or mcts:<millis>ms
+//   --computer-plays <light|dark>   Side the computer takes (dark)
+//
+// Keys: space rolls, left/right picks a move, enter plays it, u undoes, y redoes,
+// n starts a new game and q quits
+
+use std::io;
+use std::process;
+use std::time::Duration;
+
+use game_of_ur::ai::{agent_from_spec, Agent, WinEstimator};
+use game_of_ur::{GameState, Player, RuleSet, SeededDice, Variant};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+// Size of one square on screen
+const CELL_WIDTH: u16 = 7;
+const CELL_HEIGHT: u16 = 3;
+// Pause before each of the computer's rolls and moves, so they can be followed
+const COMPUTER_DELAY: Duration = Duration::from_millis(700);
+
+fn main() {
+    let mut variant = Variant::Finkel;
+    let mut seed = None;
+    let mut computer = None;
+    let mut computer_player = Player::Dark;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+        match arg.as_str() {
+            "--variant" => {
+                let name = value("--variant");
+                variant = Variant::from_name(&name).unwrap_or_else(|| fail(&format!("unknown variant '{}'", name)));
+            }
+            "--seed" => {
+                let text = value("--seed");
+                seed = Some(text.parse().unwrap_or_else(|_| fail(&format!("invalid value '{}' for --seed", text))));
+            }
+            "--computer" => computer = Some(value("--computer")),
+            "--computer-plays" => {
+                computer_player = match value("--computer-plays").to_lowercase().as_str() {
+                    "light" => Player::Light,
+                    "dark" => Player::Dark,
+                    other => fail(&format!("'{}' is not light or dark", other)),
+                }
+            }
+            "-h" | "--help" => usage(),
+            _ => fail(&format!("unknown option '{}'", arg)),
+        }
+    }
+
+    let dice = match seed {
+        Some(seed) => SeededDice::new(seed),
+        None => SeededDice::from_entropy(),
+    };
+    let computer = computer.map(|spec| {
+        let agent = agent_from_spec(&spec, dice.seed).unwrap_or_else(|| fail(&format!("unknown player '{}'", spec)));
+        (computer_player, agent)
+    });
+    let mut app = App {
+        game: GameState::with_dice(RuleSet::for_variant(variant), Box::new(dice)),
+        computer,
+        estimator: WinEstimator::new(),
+        selected: 0,
+        message: String::new(),
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    if let Err(error) = result {
+        fail(&error.to_string());
+    }
+}
+
+struct App {
+    game: GameState,
+    computer: Option<(Player, Box<dyn Agent>)>,
+    estimator: WinEstimator,  // Kept so redraws reuse its estimates
+    selected: usize,  // Index into the legal moves
+    message: String,
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            // The computer takes a step whenever no key arrives in time
+            let timeout = if self.is_computer_turn() { COMPUTER_DELAY } else { Duration::from_secs(60) };
+            if !event::poll(timeout)? {
+                if self.is_computer_turn() {
+                    self.computer_step();
+                }
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('n') => {
+                    self.game.reset();
+                    self.selected = 0;
+                    self.message = "New game".to_string();
+                }
+                KeyCode::Char('u') => self.undo(),
+                KeyCode::Char('y') => {
+                    self.game.redo();
+                    self.selected = 0;
+                }
+                _ if self.is_computer_turn() || self.game.game_over() => {}
+                KeyCode::Char(' ') | KeyCode::Char('r') => self.roll(),
+                KeyCode::Left | KeyCode::Char('h') => self.cycle(-1),
+                KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => self.cycle(1),
+                KeyCode::Enter => self.play_selected(),
+                _ => {}
+            }
+        }
+    }
+
+    fn is_computer_turn(&self) -> bool {
+        matches!(&self.computer, Some((player, _)) if *player == self.game.current_player()) && !self.game.game_over()
+    }
+
+    fn roll(&mut self) {
+        if self.game.dice_value() != 0 {
+            return;
+        }
+        let player = self.game.current_player();
+        let roll = match self.game.roll_dice() {
+            Ok(roll) => roll,
+            Err(error) => {
+                self.message = error.to_string();
+                return;
+            }
+        };
+        self.selected = 0;
+        self.message = if roll == 0 {
+            format!("{} rolled 0. Turn passes.", player.name())
+        } else if self.game.legal_moves().is_empty() {
+            self.game.pass_turn();
+            format!("{} rolled {} but nothing can move. Turn passes.", player.name(), roll)
+        } else {
+            format!("{} rolled {}", player.name(), roll)
+        };
+    }
+
+    fn cycle(&mut self, step: isize) {
+        let count = self.game.legal_moves().len() as isize;
+        if count > 0 {
+            self.selected = (self.selected as isize + step).rem_euclid(count) as usize;
+        }
+    }
+
+    fn play_selected(&mut self) {
+        let moves = self.game.legal_moves();
+        if let Some(mv) = moves.get(self.selected) {
+            self.play(mv.from);
+        }
+    }
+
+    fn play(&mut self, from: usize) {
+        let player = self.game.current_player();
+        match self.game.make_move(from) {
+            Ok(outcome) => {
+                self.selected = 0;
+                self.message = match outcome.winner {
+                    Some(winner) => format!("{} wins!", winner.name()),
+                    None if outcome.extra_turn => format!("{} rolls again", player.name()),
+                    None if outcome.mv.captures => format!("{} captured a piece", player.name()),
+                    None => String::new(),
+                };
+            }
+            Err(error) => self.message = error.to_string(),
+        }
+    }
+
+    // Against the computer, takes back its turns as well so it's your move again
+    fn undo(&mut self) {
+        if self.game.undo().is_none() {
+            return;
+        }
+        while self.is_computer_turn() && self.game.undo().is_some() {}
+        self.selected = 0;
+        self.message = String::new();
+    }
+
+    // Rolls or moves for the computer, one step at a time so each is visible
+    fn computer_step(&mut self) {
+        if self.game.dice_value() == 0 {
+            self.roll();
+            return;
+        }
+        let Some((_, agent)) = &mut self.computer else {
+            return;
+        };
+        match agent.choose_move(&self.game) {
+            Some(mv) => self.play(mv.from),
+            None => self.game.pass_turn(),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let layout = self.game.rules().layout().cells();
+        let rows = layout.iter().map(|cell| cell.row as u16 + 1).max().unwrap_or(0);
+
+        let title = format!(" Game of Ur: {} ", self.game.rules().variant.name());
+        let outer = Block::default().borders(Borders::ALL).title(title);
+        let area = outer.inner(frame.area());
+        frame.render_widget(outer, frame.area());
+
+        let [board_area, info_area] = Layout::vertical([
+            Constraint::Length(rows * CELL_HEIGHT + 1),
+            Constraint::Min(0),
+        ]).areas(area);
+        self.draw_board(frame, board_area);
+        frame.render_widget(Paragraph::new(self.info_lines()), info_area);
+    }
+
+    fn draw_board(&self, frame: &mut Frame, area: Rect) {
+        let moves = self.game.legal_moves();
+        let selected = moves.get(self.selected);
+
+        for (index, cell) in self.game.rules().layout().cells().iter().enumerate() {
+            let rect = Rect {
+                x: area.x + cell.col as u16 * CELL_WIDTH,
+                y: area.y + cell.row as u16 * CELL_HEIGHT,
+                width: CELL_WIDTH,
+                height: CELL_HEIGHT,
+            };
+            if rect.right() > area.right() || rect.bottom() > area.bottom() {
+                continue;
+            }
+
+            let mut border = Style::default();
+            if cell.rosette {
+                border = border.fg(Color::Yellow);
+            }
+            if selected.is_some_and(|mv| mv.from_index == Some(index)) {
+                border = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+            } else if selected.is_some_and(|mv| mv.to_index == Some(index)) {
+                border = Style::default().fg(Color::Green).add_modifier(Modifier::BOLD);
+            }
+
+            let content = match self.game.board()[index].piece {
+                Some(Player::Light) => Span::styled("○", Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+                Some(Player::Dark) => Span::styled("●", Style::default().fg(Color::LightRed)),
+                None if cell.rosette => Span::styled("✿", Style::default().fg(Color::Yellow)),
+                None => Span::raw(" "),
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(border)
+                .title(Span::styled((index + 1).to_string(), Style::default().fg(Color::DarkGray)));
+            frame.render_widget(Paragraph::new(content).centered().block(block), rect);
+        }
+    }
+
+    fn info_lines(&self) -> Vec<Line<'static>> {
+        let game = &self.game;
+        let mut lines = Vec::new();
+        for player in [Player::Light, Player::Dark] {
+            let mut text = format!(
+                "{:<6} {} start, {} off",
+                player.name(),
+                game.pieces_in_start(player),
+                game.pieces_off(player),
+            );
+            if let Some((_, agent)) = self.computer.as_ref().filter(|(computer, _)| *computer == player) {
+                text.push_str(&format!("  (computer: {})", agent.name()));
+            }
+            lines.push(Line::from(text));
+        }
+
+        let dice = match game.dice_value() {
+            0 => "-".to_string(),
+            value => value.to_string(),
+        };
+        let chance = 100.0 * self.estimator.win_probability(game);
+        lines.push(Line::from(format!(
+            "Turn: {}   Dice: {}   {} wins about {:.0}% of the time from here",
+            game.current_player().name(),
+            dice,
+            game.current_player().name(),
+            chance,
+        )));
+
+        let moves = game.legal_moves();
+        if let Some(mv) = moves.get(self.selected) {
+            let from = if mv.enters() { "start".to_string() } else { format!("square {}", mv.from_index.map_or(0, |index| index + 1)) };
+            let to = match mv.to_index {
+                Some(index) => format!("square {}", index + 1),
+                None => "off".to_string(),
+            };
+            lines.push(Line::from(format!("Move {} of {}: {} to {}", self.selected + 1, moves.len(), from, to)));
+        } else {
+            lines.push(Line::from(""));
+        }
+
+        let status = if self.message.is_empty() { game.status_message() } else { self.message.clone() };
+        lines.push(Line::styled(status, Style::default().fg(Color::LightBlue)));
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            "space roll  ←/→ choose  enter move  u undo  y redo  n new game  q quit",
+            Style::default().fg(Color::DarkGray),
+        ));
+        lines
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: tui [--variant <name>] [--seed <n>] [--computer <player>] [--computer-plays <light|dark>]");
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("tui: {}", message);
+    process::exit(1);
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+
+use serde::Serialize;
+
+// Where a square sits on the board grid, for rendering and for checking routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Cell {
+    pub row: u8,
+    pub col: u8,
+    pub rosette: bool,
+}
+
+impl Cell {
+    pub fn new(row: u8, col: u8) -> Cell {
+        Cell { row, col, rosette: false }
+    }
+
+    pub fn rosette(row: u8, col: u8) -> Cell {
+        Cell { row, col, rosette: true }
+    }
+
+    fn is_adjacent(&self, other: &Cell) -> bool {
+        self.row.abs_diff(other.row) + self.col.abs_diff(other.col) == 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    EmptyBoard,
+    OverlappingCells { first: usize, second: usize },
+    EmptyRoute,
+    SquareOutOfRange { index: usize },
+    RepeatedSquare { index: usize },
+    NotAdjacent { from: usize, to: usize },
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::EmptyBoard => write!(f, "board has no squares"),
+            BoardError::OverlappingCells { first, second } => {
+                write!(f, "squares {} and {} share a cell", first, second)
+            }
+            BoardError::EmptyRoute => write!(f, "route has no squares"),
+            BoardError::SquareOutOfRange { index } => write!(f, "square {} is not on the board", index),
+            BoardError::RepeatedSquare { index } => write!(f, "route visits square {} twice", index),
+            BoardError::NotAdjacent { from, to } => {
+                write!(f, "route jumps from square {} to square {}", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+// The squares of a board, indexed by board index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BoardLayout {
+    cells: Vec<Cell>,
+}
+
+impl BoardLayout {
+    pub fn new(cells: Vec<Cell>) -> Result<BoardLayout, BoardError> {
+        if cells.is_empty() {
+            return Err(BoardError::EmptyBoard);
+        }
+        for (first, a) in cells.iter().enumerate() {
+            if let Some(second) = cells[first + 1..].iter().position(|b| (a.row, a.col) == (b.row, b.col)) {
+                return Err(BoardError::OverlappingCells { first, second: first + 1 + second });
+            }
+        }
+        Ok(BoardLayout { cells })
+    }
+
+    // Original Ur board: a 3x4 block (indices 0-11), a 2-square bridge (12-13)
+    // and a 3x2 block (14-19). Light owns the top row, Dark the bottom row
+    pub fn standard() -> BoardLayout {
+        let mut cells = Vec::with_capacity(20);
+        for row in 0..3 {
+            for col in 0..4 {
+                cells.push(Cell::new(row, col));
+            }
+        }
+        cells.push(Cell::new(1, 4));
+        cells.push(Cell::new(1, 5));
+        for row in 0..3 {
+            for col in 6..8 {
+                cells.push(Cell::new(row, col));
+            }
+        }
+        // Rosettes: corners of each entry row (indices 0, 8), the central shared
+        // rosette (index 7) and the corners of the far end (indices 14, 18)
+        BoardLayout::with_rosettes(cells, &[0, 7, 8, 14, 18])
+    }
+
+    // Later 20-square board: the 3x4 block's middle row continues as a straight
+    // run of eight squares (indices 12-19)
+    pub fn aseb() -> BoardLayout {
+        let mut cells = Vec::with_capacity(20);
+        for row in 0..3 {
+            for col in 0..4 {
+                cells.push(Cell::new(row, col));
+            }
+        }
+        for col in 4..12 {
+            cells.push(Cell::new(1, col));
+        }
+        BoardLayout::with_rosettes(cells, &[0, 7, 8, 15, 19])
+    }
+
+    fn with_rosettes(mut cells: Vec<Cell>, rosettes: &[usize]) -> BoardLayout {
+        for &index in rosettes {
+            cells[index].rosette = true;
+        }
+        BoardLayout::new(cells).expect("built-in layouts are valid")
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn is_rosette(&self, index: usize) -> bool {
+        self.cells.get(index).is_some_and(|cell| cell.rosette)
+    }
+}
+
+// A player's route as the board index of each path position, starting at path 1.
+// Path 0 is the start area and the position after the last square is off the board
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Route {
+    squares: Vec<usize>,
+    #[serde(skip)]
+    positions: Vec<Option<usize>>,  // Path position of each board index
+}
+
+impl Route {
+    pub fn new(layout: &BoardLayout, squares: Vec<usize>) -> Result<Route, BoardError> {
+        if squares.is_empty() {
+            return Err(BoardError::EmptyRoute);
+        }
+
+        let mut positions = vec![None; layout.len()];
+        for (i, &index) in squares.iter().enumerate() {
+            match positions.get_mut(index) {
+                None => return Err(BoardError::SquareOutOfRange { index }),
+                Some(Some(_)) => return Err(BoardError::RepeatedSquare { index }),
+                Some(position) => *position = Some(i + 1),
+            }
+        }
+
+        // Pieces step from square to square, so consecutive squares must touch
+        for pair in squares.windows(2) {
+            if !layout.cells[pair[0]].is_adjacent(&layout.cells[pair[1]]) {
+                return Err(BoardError::NotAdjacent { from: pair[0], to: pair[1] });
+            }
+        }
+
+        Ok(Route { squares, positions })
+    }
+
+    pub fn squares(&self) -> &[usize] {
+        &self.squares
+    }
+
+    pub fn len(&self) -> usize {
+        self.squares.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.squares.is_empty()
+    }
+
+    pub fn off_position(&self) -> usize {
+        self.squares.len() + 1
+    }
+
+    pub fn board_index(&self, path_pos: usize) -> Option<usize> {
+        if path_pos == 0 {
+            return None; // Start area
+        }
+        // Positions past the end of the route are off the board
+        self.squares.get(path_pos - 1).copied()
+    }
+
+    pub fn path_position(&self, board_index: usize) -> Option<usize> {
+        self.positions.get(board_index).copied().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{RuleSet, Variant};
+    use crate::Player;
+
+    #[test]
+    fn routes_map_path_positions_to_board_indices_and_back() {
+        for variant in [Variant::Finkel, Variant::Masters, Variant::Blitz, Variant::Aseb] {
+            let rules = RuleSet::for_variant(variant);
+            for player in [Player::Light, Player::Dark] {
+                let route = rules.route(player);
+                assert_eq!(route.board_index(0), None);
+                assert_eq!(route.board_index(route.off_position()), None);
+                for path_pos in 1..route.off_position() {
+                    let index = route.board_index(path_pos).unwrap();
+                    assert_eq!(route.path_position(index), Some(path_pos));
+                }
+                // Squares off the route, like the other player's row, have no path position
+                let off_route = (0..rules.layout().len()).filter(|&index| route.path_position(index).is_none()).count();
+                assert_eq!(off_route, rules.layout().len() - route.len());
+            }
+        }
+    }
+
+    #[test]
+    fn bad_boards_and_routes_are_rejected() {
+        assert_eq!(BoardLayout::new(Vec::new()), Err(BoardError::EmptyBoard));
+        let overlapping = vec![Cell::new(0, 0), Cell::new(0, 1), Cell::new(0, 0)];
+        assert_eq!(BoardLayout::new(overlapping), Err(BoardError::OverlappingCells { first: 0, second: 2 }));
+
+        let layout = BoardLayout::standard();
+        assert_eq!(Route::new(&layout, Vec::new()).err(), Some(BoardError::EmptyRoute));
+        assert_eq!(Route::new(&layout, vec![3, 20]).err(), Some(BoardError::SquareOutOfRange { index: 20 }));
+        assert_eq!(Route::new(&layout, vec![3, 2, 3]).err(), Some(BoardError::RepeatedSquare { index: 3 }));
+        // The bridge leaves the middle row only, so the top row can't jump to it
+        assert_eq!(Route::new(&layout, vec![3, 12]).err(), Some(BoardError::NotAdjacent { from: 3, to: 12 }));
+        assert!(Route::new(&layout, vec![3, 2, 1, 0, 4]).is_ok());
+    }
+
+    #[test]
+    fn built_in_layouts_match_their_shapes() {
+        let standard = BoardLayout::standard();
+        assert_eq!(standard.len(), 20);
+        assert_eq!((standard.cells()[12].row, standard.cells()[12].col), (1, 4));
+        assert_eq!((standard.cells()[19].row, standard.cells()[19].col), (2, 7));
+
+        let aseb = BoardLayout::aseb();
+        assert_eq!(aseb.len(), 20);
+        assert!(aseb.cells()[12..].iter().enumerate().all(|(i, cell)| (cell.row, cell.col) == (1, 4 + i as u8)));
+    }
+}
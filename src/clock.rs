@@ -0,0 +1,211 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Player;
+
+// Milliseconds since some fixed point, for measuring how long something takes.
+// Date.now in the browser, a monotonic clock natively
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_millis() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub(crate) fn now_millis() -> f64 {
+    js_sys::Date::now()
+}
+
+// Without JavaScript there is no clock on wasm32, so time never passes
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+pub(crate) fn now_millis() -> f64 {
+    0.0
+}
+
+// Where game clocks read the time, in milliseconds since some fixed point
+pub trait TimeSource: fmt::Debug + Send {
+    fn now_millis(&self) -> f64;
+
+    fn clone_box(&self) -> Box<dyn TimeSource>;
+}
+
+impl Clone for Box<dyn TimeSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// The real time, from now_millis
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now_millis(&self) -> f64 {
+        now_millis()
+    }
+
+    fn clone_box(&self) -> Box<dyn TimeSource> {
+        Box::new(*self)
+    }
+}
+
+// Time that only moves when told to, e.g. to check flag-fall without waiting. Clones share
+// the same time, so keep one to advance the clock a game was given
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    millis: Arc<Mutex<f64>>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, millis: f64) {
+        *self.millis.lock().unwrap() += millis;
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now_millis(&self) -> f64 {
+        *self.millis.lock().unwrap()
+    }
+
+    fn clone_box(&self) -> Box<dyn TimeSource> {
+        Box::new(self.clone())
+    }
+}
+
+// How much time each player gets. A move runs from when a player is due to roll until
+// they've moved or passed, so an extra turn is a new move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial_ms: u64,    // On each clock at the start
+    pub increment_ms: u64,  // Fischer increment, added after each move
+    pub delay_ms: u64,      // Grace at the start of each move before the clock starts running
+}
+
+impl TimeControl {
+    pub fn sudden_death(initial_ms: u64) -> TimeControl {
+        TimeControl { initial_ms, increment_ms: 0, delay_ms: 0 }
+    }
+
+    pub fn fischer(initial_ms: u64, increment_ms: u64) -> TimeControl {
+        TimeControl { initial_ms, increment_ms, delay_ms: 0 }
+    }
+
+    pub fn with_delay(initial_ms: u64, delay_ms: u64) -> TimeControl {
+        TimeControl { initial_ms, increment_ms: 0, delay_ms }
+    }
+}
+
+// Both players' clocks, at most one of them running
+#[derive(Debug, Clone)]
+pub(crate) struct GameClock {
+    pub(crate) control: TimeControl,
+    time: Box<dyn TimeSource>,
+    remaining: [f64; 2],                 // Light, then Dark, as of the start of the running move
+    pub(crate) running: Option<Player>,  // None once the game is over
+    move_started: f64,
+}
+
+impl GameClock {
+    pub(crate) fn start(control: TimeControl, time: Box<dyn TimeSource>, player: Player) -> GameClock {
+        let move_started = time.now_millis();
+        let initial = control.initial_ms as f64;
+        GameClock { control, time, remaining: [initial, initial], running: Some(player), move_started }
+    }
+
+    // Full clocks again on the same time source, for a new game
+    pub(crate) fn restart(&self, player: Player) -> GameClock {
+        GameClock::start(self.control, self.time.clone(), player)
+    }
+
+    // Time left on the player's clock now, which may be below zero once it has run out
+    pub(crate) fn remaining(&self, player: Player) -> f64 {
+        let left = self.remaining[index(player)];
+        if self.running != Some(player) {
+            return left;
+        }
+        let elapsed = self.time.now_millis() - self.move_started;
+        left - (elapsed - self.control.delay_ms as f64).max(0.0)
+    }
+
+    // The running player, if their time is up
+    pub(crate) fn flagged(&self) -> Option<Player> {
+        self.running.filter(|&player| self.remaining(player) <= 0.0)
+    }
+
+    // Stops the clock of the player who just moved, adding the increment, and starts next's move
+    pub(crate) fn end_move(&mut self, next: Player) {
+        if let Some(moved) = self.running {
+            self.remaining[index(moved)] = self.remaining(moved) + self.control.increment_ms as f64;
+        }
+        self.running = Some(next);
+        self.move_started = self.time.now_millis();
+    }
+
+    // Runs player's clock instead, without an increment, e.g. after an undo
+    pub(crate) fn switch_to(&mut self, player: Option<Player>) {
+        if self.running == player {
+            return;
+        }
+        if let Some(running) = self.running {
+            self.remaining[index(running)] = self.remaining(running);
+        }
+        self.running = player;
+        self.move_started = self.time.now_millis();
+    }
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Light => 0,
+        Player::Dark => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn running_out_of_time_loses() {
+        let time = ManualClock::new();
+        let mut game = GameState::new(RuleSet::finkel());
+        game.set_time_control(TimeControl::sudden_death(1000), Box::new(time.clone()));
+
+        time.advance(999.0);
+        assert!(!game.check_time());
+        assert_eq!(game.remaining_millis(Player::Light), Some(1.0));
+        time.advance(1.0);
+        assert!(game.check_time());
+        assert!(game.game_over());
+        assert_eq!(game.winner(), Some(Player::Dark));
+        assert_eq!(game.remaining_millis(Player::Dark), Some(1000.0));
+    }
+
+    #[test]
+    fn increment_and_delay_are_applied_per_move() {
+        let time = ManualClock::new();
+        let control = TimeControl { initial_ms: 1000, increment_ms: 200, delay_ms: 100 };
+        let mut clock = GameClock::start(control, Box::new(time.clone()), Player::Light);
+
+        time.advance(50.0);
+        assert_eq!(clock.remaining(Player::Light), 1000.0);
+        time.advance(250.0);
+        assert_eq!(clock.remaining(Player::Light), 800.0);
+        clock.end_move(Player::Dark);
+        assert_eq!(clock.remaining(Player::Light), 1000.0);
+
+        time.advance(1100.0);
+        assert_eq!(clock.flagged(), Some(Player::Dark));
+    }
+}
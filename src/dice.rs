@@ -0,0 +1,229 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+// Increment of the SplitMix64 generator (the golden ratio in 64-bit fixed point)
+pub(crate) const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// SplitMix64 output function, mixing a counter into a well-distributed 64-bit value
+pub(crate) fn splitmix64(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub enum DiceKind {
+    FourBinary,          // Four tetrahedral dice, each marked on two of four tips (0-4)
+    ThreeBinary,         // Three binary dice (0-3)
+    ThreeBinaryZeroMax,  // Three binary dice where a roll of 0 counts as 4 (1-4)
+    Knucklebone,         // One astragalus, narrow sides landing less often (1-4)
+    D4,                  // A fair four-sided die (1-4)
+}
+
+impl DiceKind {
+    // Number of equally likely outcomes giving each value, indexed by value
+    pub fn weights(&self) -> &'static [u32] {
+        match self {
+            DiceKind::FourBinary => &[1, 4, 6, 4, 1],
+            DiceKind::ThreeBinary => &[1, 3, 3, 1],
+            DiceKind::ThreeBinaryZeroMax => &[0, 3, 3, 1, 1],
+            DiceKind::Knucklebone => &[0, 1, 4, 4, 1],
+            DiceKind::D4 => &[0, 1, 1, 1, 1],
+        }
+    }
+
+    // Whether the dice can show the value at all
+    pub fn can_roll(&self, value: u8) -> bool {
+        self.weights().get(value as usize).is_some_and(|&weight| weight > 0)
+    }
+
+    pub fn max_value(&self) -> u8 {
+        (self.weights().len() - 1) as u8
+    }
+
+    // Probability of rolling each value, indexed by value
+    pub fn probabilities(&self) -> Vec<f64> {
+        let total: u32 = self.weights().iter().sum();
+        self.weights().iter().map(|&weight| weight as f64 / total as f64).collect()
+    }
+
+    pub fn probability(&self, value: u8) -> f64 {
+        self.probabilities().get(value as usize).copied().unwrap_or(0.0)
+    }
+
+    // Values that can actually be rolled, paired with their probabilities
+    pub fn outcomes(&self) -> Vec<(u8, f64)> {
+        self.probabilities()
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, probability)| probability > 0.0)
+            .map(|(value, probability)| (value as u8, probability))
+            .collect()
+    }
+
+    // Maps a uniformly random number onto a roll
+    pub fn sample(&self, random: u64) -> u8 {
+        let total: u32 = self.weights().iter().sum();
+        let mut remaining = (random % total as u64) as u32;
+        for (value, &weight) in self.weights().iter().enumerate() {
+            if remaining < weight {
+                return value as u8;
+            }
+            remaining -= weight;
+        }
+        unreachable!("weights cover every sample")
+    }
+}
+
+pub trait DiceSource: fmt::Debug + Send {
+    // None once the source has no rolls left, e.g. scripted dice played past the script
+    fn roll(&mut self, dice: DiceKind) -> Option<u8>;
+
+    fn clone_box(&self) -> Box<dyn DiceSource>;
+
+    // Seed and stream position, for sources whose rolls can be reproduced
+    fn seed(&self) -> Option<SeededDice> {
+        None
+    }
+}
+
+impl Clone for Box<dyn DiceSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// Counter-based SplitMix64 stream: the n-th roll only depends on the seed and n,
+// so storing (seed, position) is enough to continue or replay a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeededDice {
+    pub seed: u64,
+    pub position: u64,  // Number of random values drawn so far
+}
+
+impl SeededDice {
+    pub fn new(seed: u64) -> SeededDice {
+        SeededDice { seed, position: 0 }
+    }
+
+    // Seed from the platform: Math.random in the browser, the system clock natively
+    pub fn from_entropy() -> SeededDice {
+        #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+        let seed = {
+            let high = (js_sys::Math::random() * 4294967296.0) as u64;
+            let low = (js_sys::Math::random() * 4294967296.0) as u64;
+            (high << 32) | low
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let seed = {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or_default();
+            splitmix64(nanos)
+        };
+        // Without JavaScript there is no entropy source on wasm32, so seed explicitly instead
+        #[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+        let seed = 0;
+        SeededDice::new(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.position += 1;
+        splitmix64(self.seed.wrapping_add(self.position.wrapping_mul(GOLDEN_GAMMA)))
+    }
+}
+
+impl DiceSource for SeededDice {
+    fn roll(&mut self, dice: DiceKind) -> Option<u8> {
+        Some(dice.sample(self.next_u64()))
+    }
+
+    fn clone_box(&self) -> Box<dyn DiceSource> {
+        Box::new(*self)
+    }
+
+    fn seed(&self) -> Option<SeededDice> {
+        Some(*self)
+    }
+}
+
+// Unseeded rolls from JavaScript Math.random, only usable in the web build
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsRandomDice;
+
+#[cfg(feature = "wasm")]
+impl DiceSource for JsRandomDice {
+    fn roll(&mut self, dice: DiceKind) -> Option<u8> {
+        Some(dice.sample((js_sys::Math::random() * 4294967296.0) as u64))
+    }
+
+    fn clone_box(&self) -> Box<dyn DiceSource> {
+        Box::new(*self)
+    }
+}
+
+// Replays a fixed list of rolls, e.g. from a recorded game or a test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedDice {
+    rolls: Vec<u8>,
+    position: usize,
+}
+
+impl ScriptedDice {
+    pub fn new(rolls: Vec<u8>) -> ScriptedDice {
+        ScriptedDice { rolls, position: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.rolls.len() - self.position
+    }
+}
+
+impl DiceSource for ScriptedDice {
+    // The recorded rolls are replayed as-is, whatever dice the rules use
+    fn roll(&mut self, _dice: DiceKind) -> Option<u8> {
+        let value = *self.rolls.get(self.position)?;
+        self.position += 1;
+        Some(value)
+    }
+
+    fn clone_box(&self) -> Box<dyn DiceSource> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_dice_repeat_from_seed_and_position() {
+        let mut first = SeededDice::new(42);
+        let mut second = SeededDice::new(42);
+        let rolls: Vec<_> = (0..50).map(|_| first.roll(DiceKind::FourBinary)).collect();
+        let again: Vec<_> = (0..50).map(|_| second.roll(DiceKind::FourBinary)).collect();
+        assert_eq!(rolls, again);
+
+        let mut resumed = SeededDice { seed: 42, position: 20 };
+        assert_eq!(resumed.roll(DiceKind::FourBinary), rolls[20]);
+        assert_eq!(first.seed(), Some(SeededDice { seed: 42, position: 50 }));
+    }
+
+    #[test]
+    fn scripted_dice_roll_in_order_then_run_out() {
+        let mut dice = ScriptedDice::new(vec![3, 0, 4]);
+        assert_eq!(dice.roll(DiceKind::FourBinary), Some(3));
+        assert_eq!(dice.roll(DiceKind::FourBinary), Some(0));
+        assert_eq!(dice.remaining(), 1);
+        assert_eq!(dice.roll(DiceKind::FourBinary), Some(4));
+        assert_eq!(dice.roll(DiceKind::FourBinary), None);
+        assert_eq!(dice.remaining(), 0);
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Player;
+
+// Something that happened in a game, as GameState reports it. Squares are board indexes.
+// A move gives one of PieceEntered, PieceMoved or PieceBorneOff, then PieceCaptured if it
+// took a piece, then GameWon, ExtraTurn or TurnPassed. A player out of time gives
+// TimeExpired, and one who gives up Forfeited, and then GameWon for the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    DiceRolled { player: Player, value: u8 },
+    PieceEntered { player: Player, to: usize },
+    PieceMoved { player: Player, from: usize, to: usize },
+    PieceCaptured { player: Player, at: usize },  // player is the one sent back to start
+    PieceBorneOff { player: Player, from: usize },
+    TurnPassed { player: Player },                // player is now to roll
+    ExtraTurn { player: Player },                 // player rolls again
+    TimeExpired { player: Player },
+    Forfeited { player: Player },
+    GameWon { winner: Player },
+}
+
+pub type ObserverId = u64;
+
+type Callback = Box<dyn FnMut(&GameEvent) + Send>;
+
+// Callbacks subscribed to a game. A clone of a game starts with none, so copies made to
+// search or analyse stay quiet
+#[derive(Default)]
+pub(crate) struct Observers {
+    next_id: ObserverId,
+    callbacks: Vec<(ObserverId, Callback)>,
+}
+
+impl Observers {
+    pub(crate) fn add(&mut self, callback: Callback) -> ObserverId {
+        self.next_id += 1;
+        self.callbacks.push((self.next_id, callback));
+        self.next_id
+    }
+
+    pub(crate) fn remove(&mut self, id: ObserverId) -> bool {
+        let count = self.callbacks.len();
+        self.callbacks.retain(|(observer, _)| *observer != id);
+        self.callbacks.len() != count
+    }
+
+    pub(crate) fn notify(&mut self, event: &GameEvent) {
+        for (_, callback) in &mut self.callbacks {
+            callback(event);
+        }
+    }
+}
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Observers::default()
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.callbacks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::dice::ScriptedDice;
+    use crate::game::GameState;
+    use crate::rules::RuleSet;
+
+    // Game at the position that rolls the given values, with its events cleared
+    fn game_at(notation: &str, rolls: Vec<u8>) -> GameState {
+        let mut game = GameState::from_notation(RuleSet::finkel(), notation).unwrap();
+        game.set_dice_source(Box::new(ScriptedDice::new(rolls)));
+        game.drain_events();
+        game
+    }
+
+    #[test]
+    fn rolls_moves_captures_and_passes_come_in_order() {
+        use GameEvent::*;
+        let (light, dark) = (Player::Light, Player::Dark);
+
+        // Light enters, then Dark rolls 0 and the turn comes back
+        let mut game = game_at("20 7/0 7/0 L -", vec![2, 0]);
+        game.roll_dice().unwrap();
+        game.make_move(0).unwrap();
+        game.roll_dice().unwrap();
+        assert_eq!(game.drain_events(), vec![
+            DiceRolled { player: light, value: 2 },
+            PieceEntered { player: light, to: 2 },
+            TurnPassed { player: dark },
+            DiceRolled { player: dark, value: 0 },
+            TurnPassed { player: light },
+        ]);
+
+        // Light takes Dark on board 6, then Dark rolls and passes
+        let mut game = game_at("5LD13 6/0 6/0 L -", vec![1, 4]);
+        game.roll_dice().unwrap();
+        game.make_move(6).unwrap();
+        game.roll_dice().unwrap();
+        game.pass_turn();
+        assert_eq!(game.drain_events(), vec![
+            DiceRolled { player: light, value: 1 },
+            PieceMoved { player: light, from: 5, to: 6 },
+            PieceCaptured { player: dark, at: 6 },
+            TurnPassed { player: dark },
+            DiceRolled { player: dark, value: 4 },
+            TurnPassed { player: light },
+        ]);
+    }
+
+    #[test]
+    fn rosettes_and_the_last_piece_off_end_the_move_differently() {
+        use GameEvent::*;
+        let light = Player::Light;
+
+        let mut game = game_at("1L18 6/0 7/0 L -", vec![1]);
+        game.roll_dice().unwrap();
+        game.make_move(3).unwrap();
+        assert_eq!(game.drain_events()[1..], [PieceMoved { player: light, from: 1, to: 0 }, ExtraTurn { player: light }]);
+
+        let mut game = game_at("15L4 0/6 7/0 L -", vec![2]);
+        game.roll_dice().unwrap();
+        game.make_move(13).unwrap();
+        assert_eq!(game.drain_events()[1..], [PieceBorneOff { player: light, from: 15 }, GameWon { winner: light }]);
+    }
+
+    #[test]
+    fn observers_hear_what_drain_events_returns() {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let mut game = game_at("20 7/0 7/0 L -", vec![3, 1]);
+        let log = Arc::clone(&heard);
+        let id = game.subscribe(move |event| log.lock().unwrap().push(*event));
+        game.roll_dice().unwrap();
+        game.make_move(0).unwrap();
+        assert_eq!(*heard.lock().unwrap(), game.drain_events());
+
+        assert!(game.unsubscribe(id));
+        assert!(!game.unsubscribe(id));
+        game.roll_dice().unwrap();
+        assert_eq!(heard.lock().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::dice::DiceKind;
+use crate::game::Player;
+
+// Commit-reveal dice for games where neither side trusts the other to roll. For each roll
+// both players pick a random secret and publish its hash (the commitment). Once both
+// commitments are in, both reveal their secrets; each is checked against its commitment
+// and the roll is read from the hash of both secrets together. Neither player can steer
+// the roll, since their secret is fixed before they see the other's, and whoever relays
+// the messages can't either, since it never contributes. Anyone holding the four values
+// can check the roll with the same code, compiled to WASM in the browser.
+//
+// A player who sees the other's secret first can still work out the roll and walk away
+// rather than reveal theirs, so hosts should treat an unfinished roll as abandoned.
+
+// Domain separators, so commitments and rolls can't be mistaken for other hashes
+const COMMITMENT_TAG: &[u8] = b"game-of-ur commitment";
+const ROLL_TAG: &[u8] = b"game-of-ur roll";
+
+// A player's random contribution to one roll
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Secret([u8; 32]);
+
+// Hash of a secret, published before any secret is revealed
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Commitment([u8; 32]);
+
+impl Secret {
+    pub fn from_bytes(bytes: [u8; 32]) -> Secret {
+        Secret(bytes)
+    }
+
+    // From the platform's secure random source (crypto.getRandomValues in the browser)
+    pub fn random() -> Secret {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes).expect("the platform has a random source");
+        Secret(bytes)
+    }
+
+    // Commitments are tied to their roll, so a secret revealed once is no use later
+    pub fn commitment(&self, round: u64) -> Commitment {
+        let mut hasher = Sha256::new();
+        hasher.update(COMMITMENT_TAG);
+        hasher.update(round.to_le_bytes());
+        hasher.update(self.0);
+        Commitment(hasher.finalize().into())
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    pub fn from_hex(text: &str) -> Result<Secret, FairDiceError> {
+        from_hex(text).map(Secret)
+    }
+}
+
+impl Commitment {
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    pub fn from_hex(text: &str) -> Result<Commitment, FairDiceError> {
+        from_hex(text).map(Commitment)
+    }
+}
+
+// Secrets are printed in full only when asked for with to_hex
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+impl fmt::Debug for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Commitment({})", self.to_hex())
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+// Both travel as lowercase hex strings
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Secret::from_hex(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Commitment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Commitment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Commitment::from_hex(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FairDiceError {
+    InvalidHex,                     // Not 64 hex digits
+    AlreadyCommitted(Player),
+    NotAllCommitted,                // Secrets are only revealed once both commitments are in
+    AlreadyRevealed(Player),
+    CommitmentMismatch(Player),     // The secret doesn't hash to the player's commitment
+    ValueMismatch { claimed: u8, actual: u8 },
+    Unfinished,                     // Checking a roll that is still missing secrets
+}
+
+impl fmt::Display for FairDiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FairDiceError::InvalidHex => write!(f, "expected 64 hex digits"),
+            FairDiceError::AlreadyCommitted(player) => write!(f, "{} has already committed", player.name()),
+            FairDiceError::NotAllCommitted => write!(f, "both players must commit before revealing"),
+            FairDiceError::AlreadyRevealed(player) => write!(f, "{} has already revealed", player.name()),
+            FairDiceError::CommitmentMismatch(player) => {
+                write!(f, "{}'s secret doesn't match their commitment", player.name())
+            }
+            FairDiceError::ValueMismatch { claimed, actual } => {
+                write!(f, "the roll was given as {} but the secrets roll {}", claimed, actual)
+            }
+            FairDiceError::Unfinished => write!(f, "the roll is missing secrets"),
+        }
+    }
+}
+
+impl std::error::Error for FairDiceError {}
+
+// One roll as it is agreed, which doubles as the transcript for checking it afterwards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FairRoll {
+    pub round: u64,                          // Numbers the rolls, so no commitment is good for two
+    pub dice: DiceKind,
+    pub commitments: [Option<Commitment>; 2],  // Light, then Dark
+    pub secrets: [Option<Secret>; 2],
+    pub value: Option<u8>,                   // Once both secrets are revealed
+}
+
+impl FairRoll {
+    pub fn new(round: u64, dice: DiceKind) -> FairRoll {
+        FairRoll { round, dice, commitments: [None, None], secrets: [None, None], value: None }
+    }
+
+    pub fn commitment(&self, player: Player) -> Option<Commitment> {
+        self.commitments[index(player)]
+    }
+
+    pub fn secret(&self, player: Player) -> Option<Secret> {
+        self.secrets[index(player)]
+    }
+
+    pub fn all_committed(&self) -> bool {
+        self.commitments.iter().all(Option::is_some)
+    }
+
+    pub fn commit(&mut self, player: Player, commitment: Commitment) -> Result<(), FairDiceError> {
+        let slot = &mut self.commitments[index(player)];
+        if slot.is_some() {
+            return Err(FairDiceError::AlreadyCommitted(player));
+        }
+        *slot = Some(commitment);
+        Ok(())
+    }
+
+    // Checks the secret against its commitment; the roll once both are revealed
+    pub fn reveal(&mut self, player: Player, secret: Secret) -> Result<Option<u8>, FairDiceError> {
+        if !self.all_committed() {
+            return Err(FairDiceError::NotAllCommitted);
+        }
+        if self.secret(player).is_some() {
+            return Err(FairDiceError::AlreadyRevealed(player));
+        }
+        if self.commitment(player) != Some(secret.commitment(self.round)) {
+            return Err(FairDiceError::CommitmentMismatch(player));
+        }
+        self.secrets[index(player)] = Some(secret);
+        if let [Some(light), Some(dark)] = self.secrets {
+            self.value = Some(roll_value(self.dice, self.round, &light, &dark));
+        }
+        Ok(self.value)
+    }
+
+    // Checks a finished roll from its transcript, e.g. one sent by a server
+    pub fn verify(&self) -> Result<u8, FairDiceError> {
+        let (Some(value), [Some(light), Some(dark)]) = (self.value, self.secrets) else {
+            return Err(FairDiceError::Unfinished);
+        };
+        for (player, secret) in [(Player::Light, light), (Player::Dark, dark)] {
+            if self.commitment(player) != Some(secret.commitment(self.round)) {
+                return Err(FairDiceError::CommitmentMismatch(player));
+            }
+        }
+        let actual = roll_value(self.dice, self.round, &light, &dark);
+        if actual != value {
+            return Err(FairDiceError::ValueMismatch { claimed: value, actual });
+        }
+        Ok(value)
+    }
+}
+
+// The roll both secrets agree on. For binary dice each bit of the combined hash is one
+// die landing marked side up or not; other dice sample from its first eight bytes
+pub fn roll_value(dice: DiceKind, round: u64, light: &Secret, dark: &Secret) -> u8 {
+    let mut hasher = Sha256::new();
+    hasher.update(ROLL_TAG);
+    hasher.update(round.to_le_bytes());
+    hasher.update(light.0);
+    hasher.update(dark.0);
+    let hash: [u8; 32] = hasher.finalize().into();
+
+    let marked = |dice: u32| (hash[0] & ((1 << dice) - 1)).count_ones() as u8;
+    match dice {
+        DiceKind::FourBinary => marked(4),
+        DiceKind::ThreeBinary => marked(3),
+        DiceKind::ThreeBinaryZeroMax => match marked(3) {
+            0 => 4,
+            value => value,
+        },
+        DiceKind::Knucklebone | DiceKind::D4 => {
+            dice.sample(u64::from_le_bytes(hash[..8].try_into().expect("hash has 8 bytes")))
+        }
+    }
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Light => 0,
+        Player::Dark => 1,
+    }
+}
+
+fn to_hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Result<[u8; 32], FairDiceError> {
+    if text.len() != 64 || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(FairDiceError::InvalidHex);
+    }
+    let mut bytes = [0; 32];
+    for (byte, pair) in bytes.iter_mut().zip(text.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| FairDiceError::InvalidHex)?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| FairDiceError::InvalidHex)?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> (Secret, Secret) {
+        (Secret::from_bytes([1; 32]), Secret::from_bytes([2; 32]))
+    }
+
+    #[test]
+    fn both_reveals_give_a_roll_that_verifies() {
+        let (light, dark) = secrets();
+        let mut roll = FairRoll::new(3, DiceKind::FourBinary);
+        roll.commit(Player::Light, light.commitment(3)).unwrap();
+        assert_eq!(roll.reveal(Player::Light, light), Err(FairDiceError::NotAllCommitted));
+        roll.commit(Player::Dark, dark.commitment(3)).unwrap();
+
+        assert_eq!(roll.reveal(Player::Dark, dark), Ok(None));
+        let value = roll.reveal(Player::Light, light).unwrap().unwrap();
+        assert_eq!(value, roll_value(DiceKind::FourBinary, 3, &light, &dark));
+        assert_eq!(roll.verify(), Ok(value));
+    }
+
+    #[test]
+    fn secrets_and_values_that_do_not_match_are_caught() {
+        let (light, dark) = secrets();
+        let mut roll = FairRoll::new(1, DiceKind::ThreeBinaryZeroMax);
+        roll.commit(Player::Light, light.commitment(1)).unwrap();
+        roll.commit(Player::Dark, dark.commitment(2)).unwrap();
+        assert_eq!(roll.reveal(Player::Dark, dark), Err(FairDiceError::CommitmentMismatch(Player::Dark)));
+        assert_eq!(roll.verify(), Err(FairDiceError::Unfinished));
+
+        roll.commitments[1] = Some(dark.commitment(1));
+        roll.reveal(Player::Light, light).unwrap();
+        let value = roll.reveal(Player::Dark, dark).unwrap().unwrap();
+        roll.value = Some(value % 4 + 1);
+        assert_eq!(roll.verify(), Err(FairDiceError::ValueMismatch { claimed: value % 4 + 1, actual: value }));
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let (light, _) = secrets();
+        assert_eq!(Secret::from_hex(&light.to_hex()), Ok(light));
+        assert_eq!(Commitment::from_hex("12"), Err(FairDiceError::InvalidHex));
+    }
+}
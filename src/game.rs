@@ -0,0 +1,768 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::clock::{GameClock, TimeControl, TimeSource};
+use crate::dice::{DiceSource, SeededDice};
+use crate::events::{GameEvent, ObserverId, Observers};
+use crate::history::{Action, History};
+use crate::moves::{Move, MoveError, MoveOutcome};
+use crate::rules::RuleSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub enum Player {
+    Light,
+    Dark,
+}
+
+impl Player {
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::Light => Player::Dark,
+            Player::Dark => Player::Light,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Player::Light => "Light",
+            Player::Dark => "Dark",
+        }
+    }
+}
+
+// How a game ended other than by a player bearing off every piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Termination {
+    TimeForfeit,  // The loser's clock ran out
+    Abandoned,    // The loser forfeited, e.g. by leaving
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Square {
+    pub piece: Option<Player>,
+    pub rosette: bool,  // Landing here grants another roll
+}
+
+// Everything undo/redo needs to put a game back exactly as it was
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    dice: Box<dyn DiceSource>,
+    board: Vec<Square>,
+    light_pieces_off: u8,
+    dark_pieces_off: u8,
+    light_pieces_start: u8,
+    dark_pieces_start: u8,
+    current_player: Player,
+    dice_value: u8,
+    game_over: bool,
+    winner: Option<Player>,
+    termination: Option<Termination>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameState {
+    rules: Arc<RuleSet>,
+    dice: Box<dyn DiceSource>,
+    board: Vec<Square>,
+    light_pieces_off: u8,  // Pieces that have exited the board
+    dark_pieces_off: u8,
+    light_pieces_start: u8,  // Pieces still in start area (position 0)
+    dark_pieces_start: u8,
+    current_player: Player,
+    dice_value: u8,
+    game_over: bool,
+    winner: Option<Player>,
+    termination: Option<Termination>,  // Set when the game ends without a win on the board
+    history: History,
+    events: Vec<GameEvent>,  // Reported since the last drain_events
+    observers: Observers,
+    clock: Option<GameClock>,  // Set by set_time_control
+}
+
+impl GameState {
+    pub fn new(rules: RuleSet) -> GameState {
+        GameState::with_dice(rules, Box::new(SeededDice::from_entropy()))
+    }
+
+    pub fn with_dice(rules: RuleSet, dice: Box<dyn DiceSource>) -> GameState {
+        let board = rules.layout().cells().iter().map(|cell| Square {
+            piece: None,
+            rosette: cell.rosette,
+        }).collect();
+
+        GameState {
+            board,
+            light_pieces_off: 0,
+            dark_pieces_off: 0,
+            light_pieces_start: rules.pieces,
+            dark_pieces_start: rules.pieces,
+            current_player: Player::Light,
+            dice_value: 0,
+            game_over: false,
+            winner: None,
+            termination: None,
+            rules: Arc::new(rules),
+            dice,
+            history: History::default(),
+            events: Vec::new(),
+            observers: Observers::default(),
+            clock: None,
+        }
+    }
+
+    // Game at the given position with a fresh history. Start counts and the result follow
+    // from the pieces; callers check that the counts fit the rule set
+    pub(crate) fn from_parts(
+        rules: RuleSet,
+        pieces: &[Option<Player>],
+        light_pieces_off: u8,
+        dark_pieces_off: u8,
+        current_player: Player,
+        dice_value: u8,
+    ) -> GameState {
+        let mut game = GameState::new(rules);
+        for (square, &piece) in game.board.iter_mut().zip(pieces) {
+            square.piece = piece;
+        }
+        game.light_pieces_off = light_pieces_off;
+        game.dark_pieces_off = dark_pieces_off;
+        game.light_pieces_start = game.rules.pieces - light_pieces_off - game.count_pieces_on_board(Player::Light);
+        game.dark_pieces_start = game.rules.pieces - dark_pieces_off - game.count_pieces_on_board(Player::Dark);
+        game.current_player = current_player;
+        game.dice_value = dice_value;
+        game.check_win_condition();
+        game
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn dice_source(&self) -> &dyn DiceSource {
+        self.dice.as_ref()
+    }
+
+    pub fn set_dice_source(&mut self, dice: Box<dyn DiceSource>) {
+        self.dice = dice;
+    }
+
+    pub fn board(&self) -> &[Square] {
+        &self.board
+    }
+
+    pub fn current_player(&self) -> Player {
+        self.current_player
+    }
+
+    pub fn dice_value(&self) -> u8 {
+        self.dice_value
+    }
+
+    pub fn game_over(&self) -> bool {
+        self.game_over
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    // None while the game goes on, or once a player has borne off every piece
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+
+    pub fn light_pieces_start(&self) -> u8 {
+        self.light_pieces_start
+    }
+
+    pub fn dark_pieces_start(&self) -> u8 {
+        self.dark_pieces_start
+    }
+
+    pub fn light_pieces_off(&self) -> u8 {
+        self.light_pieces_off
+    }
+
+    pub fn dark_pieces_off(&self) -> u8 {
+        self.dark_pieces_off
+    }
+
+    pub fn pieces_in_start(&self, player: Player) -> u8 {
+        match player {
+            Player::Light => self.light_pieces_start,
+            Player::Dark => self.dark_pieces_start,
+        }
+    }
+
+    pub fn pieces_off(&self, player: Player) -> u8 {
+        match player {
+            Player::Light => self.light_pieces_off,
+            Player::Dark => self.dark_pieces_off,
+        }
+    }
+
+    pub fn count_pieces_on_board(&self, player: Player) -> u8 {
+        self.board.iter().filter(|square| square.piece == Some(player)).count() as u8
+    }
+
+    // The roll, or the one already made this turn. Fails if the game is over, the mover's
+    // clock has run out, or the dice source has run out, leaving the dice unrolled
+    pub fn roll_dice(&mut self) -> Result<u8, MoveError> {
+        if self.check_time() {
+            return Err(MoveError::OutOfTime);
+        }
+        if self.game_over {
+            return Err(MoveError::GameOver);
+        }
+        // Only one roll per turn
+        if self.dice_value != 0 {
+            return Ok(self.dice_value);
+        }
+
+        let before = (!self.history.is_paused()).then(|| self.snapshot());
+        let player = self.current_player;
+        // Roll the dice of the rule set (4 tetrahedral dice by default, total 0-4)
+        let total = self.dice.roll(self.rules.dice).ok_or(MoveError::NoRollsLeft)?;
+        if let Some(before) = before {
+            self.history.record(Action::Roll { player, value: total }, before);
+        }
+        self.emit(GameEvent::DiceRolled { player, value: total });
+        self.set_roll(total);
+        Ok(self.dice_value)
+    }
+
+    // Takes a roll without drawing from the dice source or recording it, so searches can
+    // try each outcome. Call it only when roll_dice could be called
+    pub(crate) fn set_roll(&mut self, value: u8) {
+        // Rule: A roll of 0 can't move anything, so the turn passes straight away
+        if value == 0 {
+            self.dice_value = 0;
+            self.switch_player();
+        } else {
+            self.dice_value = value;
+        }
+    }
+
+    pub fn board_index_to_path(&self, square_index: usize, player: Player) -> Option<usize> {
+        self.rules.route(player).path_position(square_index)
+    }
+
+    pub fn path_to_board_index(&self, path_pos: usize, player: Player) -> Option<usize> {
+        self.rules.route(player).board_index(path_pos)
+    }
+
+    fn check_landing(&self, board_idx: usize, player: Player) -> Result<(), MoveError> {
+        match self.board[board_idx].piece {
+            // Blocked by own piece
+            Some(piece_player) if piece_player == player => Err(MoveError::BlockedByOwnPiece),
+            // Rule: A piece on a rosette is safe, so the shared central rosette can't be captured
+            Some(_) if self.rules.safe_rosettes && self.board[board_idx].rosette => Err(MoveError::ProtectedRosette),
+            _ => Ok(()),
+        }
+    }
+
+    // Legal moves for the current roll, in path order starting with the start area
+    pub fn legal_moves(&self) -> Vec<Move> {
+        (0..self.rules.off_position(self.current_player))
+            .filter_map(|path_pos| self.check_move(path_pos).ok())
+            .collect()
+    }
+
+    // Describes moving the current player's piece at from_path_pos, or why it can't move
+    pub fn check_move(&self, from_path_pos: usize) -> Result<Move, MoveError> {
+        if self.game_over {
+            return Err(MoveError::GameOver);
+        }
+        if self.dice_value == 0 {
+            return Err(MoveError::NoDiceRolled);
+        }
+
+        let player = self.current_player;
+
+        let from_index = if from_path_pos == 0 {
+            // Moving from start area
+            if self.pieces_in_start(player) == 0 {
+                return Err(MoveError::NoPiecesInStart);
+            }
+            None
+        } else {
+            // Moving piece on board, which must belong to current player
+            let from_board = self.path_to_board_index(from_path_pos, player)
+                .ok_or(MoveError::InvalidPathPosition)?;
+            match self.board[from_board].piece {
+                Some(piece_player) if piece_player == player => {}
+                Some(_) => return Err(MoveError::NotYourPiece),
+                None => return Err(MoveError::EmptySquare),
+            }
+            Some(from_board)
+        };
+
+        let to_path_pos = from_path_pos + self.dice_value as usize;
+        let off_path_pos = self.rules.off_position(player);
+
+        // Check if moving off board
+        if to_path_pos >= off_path_pos {
+            // Rule: To exit the board, you roll the exact number needed (or more, if the rules allow it)
+            if from_path_pos == 0 {
+                return Err(MoveError::InvalidPathPosition);
+            }
+            if to_path_pos != off_path_pos && self.rules.exact_bear_off {
+                return Err(MoveError::ExactRollRequired);
+            }
+            return Ok(Move {
+                player,
+                from: from_path_pos,
+                to: off_path_pos,
+                from_index,
+                to_index: None,
+                captures: false,
+                rosette: false,
+                bears_off: true,
+            });
+        }
+
+        let to_board = self.path_to_board_index(to_path_pos, player)
+            .ok_or(MoveError::InvalidPathPosition)?;
+        self.check_landing(to_board, player)?;
+
+        Ok(Move {
+            player,
+            from: from_path_pos,
+            to: to_path_pos,
+            from_index,
+            to_index: Some(to_board),
+            captures: self.board[to_board].piece.is_some(),
+            rosette: self.board[to_board].rosette,
+            bears_off: false,
+        })
+    }
+
+    pub fn can_move(&self, from_path_pos: usize) -> bool {
+        self.check_move(from_path_pos).is_ok()
+    }
+
+    pub fn make_move(&mut self, from_path_pos: usize) -> Result<MoveOutcome, MoveError> {
+        if self.check_time() {
+            return Err(MoveError::OutOfTime);
+        }
+        let mv = self.check_move(from_path_pos)?;
+        self.log_action(Action::Move { mv });
+        Ok(self.apply_move(&mv))
+    }
+
+    // Plays a move from check_move or legal_moves for the current roll
+    pub(crate) fn apply_move(&mut self, mv: &Move) -> MoveOutcome {
+        let player = mv.player;
+
+        // Lift the piece from the start area or from its square
+        match mv.from_index {
+            Some(from_board) => self.board[from_board].piece = None,
+            None => match player {
+                Player::Light => self.light_pieces_start -= 1,
+                Player::Dark => self.dark_pieces_start -= 1,
+            },
+        }
+
+        let to_board = match mv.to_index {
+            Some(board_idx) => board_idx,
+            None => {
+                // Rule: Pieces bear off from the board, never straight from the start
+                let from = mv.from_index.expect("pieces bear off from the board");
+                self.emit(GameEvent::PieceBorneOff { player, from });
+                // Moving off board
+                match player {
+                    Player::Light => self.light_pieces_off += 1,
+                    Player::Dark => self.dark_pieces_off += 1,
+                }
+                self.dice_value = 0;
+                self.check_win_condition();
+                match self.winner {
+                    Some(winner) => {
+                        self.stop_clock();
+                        self.emit(GameEvent::GameWon { winner });
+                    }
+                    None => self.switch_player(),
+                }
+                return MoveOutcome { mv: *mv, extra_turn: false, winner: self.winner };
+            }
+        };
+
+        self.emit(match mv.from_index {
+            Some(from) => GameEvent::PieceMoved { player, from, to: to_board },
+            None => GameEvent::PieceEntered { player, to: to_board },
+        });
+
+        // Handle capturing opponent piece, sending it back to start
+        if mv.captures {
+            self.emit(GameEvent::PieceCaptured { player: player.opponent(), at: to_board });
+            match player {
+                Player::Light => self.dark_pieces_start += 1,
+                Player::Dark => self.light_pieces_start += 1,
+            }
+        }
+
+        // Place piece
+        self.board[to_board].piece = Some(player);
+
+        // Rule: Landing on a rosette (or capturing, in some variants) grants another roll,
+        // otherwise turn passes to opponent
+        let extra_turn = (self.rules.rosettes_grant_extra_roll && mv.rosette)
+            || (self.rules.captures_grant_extra_roll && mv.captures);
+        self.dice_value = 0;
+        if extra_turn {
+            if let Some(clock) = &mut self.clock {
+                clock.end_move(player);
+            }
+            self.emit(GameEvent::ExtraTurn { player });
+        } else {
+            self.switch_player();
+        }
+        self.check_win_condition();
+        MoveOutcome { mv: *mv, extra_turn, winner: self.winner }
+    }
+
+    pub fn pass_turn(&mut self) {
+        self.check_time();
+        // Pass turn when no valid moves available
+        if self.dice_value != 0 && !self.game_over {
+            self.log_action(Action::Pass { player: self.current_player });
+            self.dice_value = 0;
+            self.switch_player();
+        }
+    }
+
+    // Actions played so far, oldest first (undone actions are left out)
+    pub fn history(&self) -> Vec<Action> {
+        self.history.actions().copied().collect()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    // Takes back the last roll, move or pass, returning it
+    pub fn undo(&mut self) -> Option<Action> {
+        let (action, before) = self.history.undo(self.snapshot())?;
+        self.restore(before);
+        Some(action)
+    }
+
+    // Plays the last undone action again, returning it
+    pub fn redo(&mut self) -> Option<Action> {
+        let (action, after) = self.history.redo(self.snapshot())?;
+        self.restore(after);
+        Some(action)
+    }
+
+    // Copy of the game that doesn't keep a history, for searching through moves cheaply
+    pub(crate) fn detached(&self) -> GameState {
+        GameState {
+            rules: Arc::clone(&self.rules),
+            dice: self.dice.clone(),
+            board: self.board.clone(),
+            light_pieces_off: self.light_pieces_off,
+            dark_pieces_off: self.dark_pieces_off,
+            light_pieces_start: self.light_pieces_start,
+            dark_pieces_start: self.dark_pieces_start,
+            current_player: self.current_player,
+            dice_value: self.dice_value,
+            game_over: self.game_over,
+            winner: self.winner,
+            termination: self.termination,
+            history: History::paused(),
+            events: Vec::new(),
+            observers: Observers::default(),
+            clock: None,
+        }
+    }
+
+    // Events since the last call, oldest first. Undo, redo and reset don't report any
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Gives both players clocks under the time control, starting with the player to move's
+    // now. Time is read from the given source, e.g. SystemClock
+    pub fn set_time_control(&mut self, control: TimeControl, time: Box<dyn TimeSource>) {
+        self.clock = Some(GameClock::start(control, time, self.current_player));
+        if self.game_over {
+            self.stop_clock();
+        }
+    }
+
+    pub fn time_control(&self) -> Option<TimeControl> {
+        self.clock.as_ref().map(|clock| clock.control)
+    }
+
+    // Milliseconds left on the player's clock, if the game has clocks
+    pub fn remaining_millis(&self, player: Player) -> Option<f64> {
+        self.clock.as_ref().map(|clock| clock.remaining(player).max(0.0))
+    }
+
+    // The player whose clock is running: the one to move unless charge_clock said otherwise,
+    // and nobody once the game is over
+    pub fn clock_running(&self) -> Option<Player> {
+        self.clock.as_ref().and_then(|clock| clock.running)
+    }
+
+    // Runs player's clock instead, without an increment, e.g. the opponent's while the game
+    // waits on their part of a shared roll. Charge the player to move again before they play
+    pub fn charge_clock(&mut self, player: Player) {
+        if self.game_over {
+            return;
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.switch_to(Some(player));
+        }
+    }
+
+    // Ends the game if the player whose clock is running has run out of time, which is
+    // otherwise noticed at the next roll, move or pass. True if that just happened
+    pub fn check_time(&mut self) -> bool {
+        if self.game_over {
+            return false;
+        }
+        let Some(player) = self.clock.as_ref().and_then(GameClock::flagged) else {
+            return false;
+        };
+        self.lose(player, Termination::TimeForfeit);
+        true
+    }
+
+    // Ends the game as a loss for player, e.g. one who abandoned it. False if it was
+    // already over
+    pub fn forfeit(&mut self, player: Player) -> bool {
+        if self.game_over {
+            return false;
+        }
+        self.lose(player, Termination::Abandoned);
+        true
+    }
+
+    // Rule: Running out of time or forfeiting loses, whatever the position. Only call it
+    // while the game goes on
+    pub(crate) fn lose(&mut self, player: Player, termination: Termination) {
+        let winner = player.opponent();
+        self.game_over = true;
+        self.winner = Some(winner);
+        self.termination = Some(termination);
+        self.dice_value = 0;
+        self.stop_clock();
+        self.emit(match termination {
+            Termination::TimeForfeit => GameEvent::TimeExpired { player },
+            Termination::Abandoned => GameEvent::Forfeited { player },
+        });
+        self.emit(GameEvent::GameWon { winner });
+    }
+
+    // Calls observer with each event as it happens, before the call that caused it returns.
+    // Observers see the same events as drain_events, which still queues them
+    pub fn subscribe(&mut self, observer: impl FnMut(&GameEvent) + Send + 'static) -> ObserverId {
+        self.observers.add(Box::new(observer))
+    }
+
+    // False if there was no such observer
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
+
+    // Searches play on copies without a history, and don't report events either
+    fn emit(&mut self, event: GameEvent) {
+        if !self.history.is_paused() {
+            self.observers.notify(&event);
+            self.events.push(event);
+        }
+    }
+
+    // Logs an action about to be played, with the state before it
+    fn log_action(&mut self, action: Action) {
+        if !self.history.is_paused() {
+            self.history.record(action, self.snapshot());
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            dice: self.dice.clone(),
+            board: self.board.clone(),
+            light_pieces_off: self.light_pieces_off,
+            dark_pieces_off: self.dark_pieces_off,
+            light_pieces_start: self.light_pieces_start,
+            dark_pieces_start: self.dark_pieces_start,
+            current_player: self.current_player,
+            dice_value: self.dice_value,
+            game_over: self.game_over,
+            winner: self.winner,
+            termination: self.termination,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.dice = snapshot.dice;
+        self.board = snapshot.board;
+        self.light_pieces_off = snapshot.light_pieces_off;
+        self.dark_pieces_off = snapshot.dark_pieces_off;
+        self.light_pieces_start = snapshot.light_pieces_start;
+        self.dark_pieces_start = snapshot.dark_pieces_start;
+        self.current_player = snapshot.current_player;
+        self.dice_value = snapshot.dice_value;
+        self.game_over = snapshot.game_over;
+        self.winner = snapshot.winner;
+        self.termination = snapshot.termination;
+        // Undo doesn't give time back, it only makes the clocks follow the turn
+        let running = (!self.game_over).then_some(self.current_player);
+        if let Some(clock) = &mut self.clock {
+            clock.switch_to(running);
+        }
+    }
+
+    fn switch_player(&mut self) {
+        self.current_player = self.current_player.opponent();
+        if let Some(clock) = &mut self.clock {
+            clock.end_move(self.current_player);
+        }
+        self.emit(GameEvent::TurnPassed { player: self.current_player });
+    }
+
+    fn stop_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.switch_to(None);
+        }
+    }
+
+    fn check_win_condition(&mut self) {
+        if self.light_pieces_off == self.rules.pieces {
+            self.game_over = true;
+            self.winner = Some(Player::Light);
+        } else if self.dark_pieces_off == self.rules.pieces {
+            self.game_over = true;
+            self.winner = Some(Player::Dark);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        // Keep the dice source so a seeded stream carries on into the next game, and the
+        // observers and time control so they carry on too
+        let dice = self.dice.clone();
+        let observers = std::mem::take(&mut self.observers);
+        let clock = self.clock.as_ref().map(|clock| clock.restart(Player::Light));
+        *self = GameState::with_dice(self.rules.as_ref().clone(), dice);
+        self.observers = observers;
+        self.clock = clock;
+    }
+
+    pub fn status_message(&self) -> String {
+        if self.game_over {
+            match self.winner {
+                Some(winner) => format!("Game Over! {} Player Wins!", winner.name()),
+                None => "Game Over!".to_string(),
+            }
+        } else if self.dice_value == 0 {
+            String::new()
+        } else {
+            "Select a piece to move".to_string()
+        }
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::new(RuleSet::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn landing_on_a_rosette_gives_another_roll() {
+        // Light on path 3 (board 1), one step short of its entry rosette
+        let mut game = GameState::from_notation(RuleSet::finkel(), "1L18 6/0 7/0 L 1").unwrap();
+        let outcome = game.make_move(3).unwrap();
+        assert!(outcome.mv.rosette && outcome.extra_turn);
+        assert_eq!((game.current_player(), game.dice_value()), (Player::Light, 0));
+        assert_eq!(game.board()[0].piece, Some(Player::Light));
+    }
+
+    #[test]
+    fn the_central_rosette_protects_its_piece() {
+        // Light on path 6 (board 5) and 7 (board 6), Dark on the central rosette (board 7)
+        let position = "5LLD12 5/0 6/0 L 2";
+        let game = GameState::from_notation(RuleSet::finkel(), position).unwrap();
+        assert_eq!(game.check_move(6), Err(MoveError::ProtectedRosette));
+
+        // A piece off the rosette can be taken
+        let mut game = GameState::from_notation(RuleSet::finkel(), "5LD13 6/0 6/0 L 1").unwrap();
+        let outcome = game.make_move(6).unwrap();
+        assert!(outcome.mv.captures && !outcome.extra_turn);
+        assert_eq!(game.pieces_in_start(Player::Dark), 7);
+
+        // Without safe rosettes, as in Blitz, the rosette can be taken too
+        let mut game = GameState::from_notation(RuleSet::blitz(), "5LLD12 3/0 4/0 L 2").unwrap();
+        let outcome = game.make_move(6).unwrap();
+        assert!(outcome.mv.captures && outcome.mv.rosette && outcome.extra_turn);
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_whole_game() {
+        let mut game = GameState::with_dice(RuleSet::finkel(), Box::new(SeededDice::new(7)));
+        let mut positions = vec![game.to_notation()];
+        while !game.game_over() {
+            if game.dice_value() == 0 {
+                game.roll_dice().unwrap();
+            } else if let Some(mv) = game.legal_moves().first() {
+                game.make_move(mv.from).unwrap();
+            } else {
+                game.pass_turn();
+            }
+            positions.push(game.to_notation());
+        }
+
+        for position in positions.iter().rev().skip(1) {
+            assert!(game.undo().is_some());
+            assert_eq!(&game.to_notation(), position);
+        }
+        assert!(!game.can_undo());
+        assert!(game.undo().is_none());
+
+        for position in &positions[1..] {
+            assert!(game.redo().is_some());
+            assert_eq!(&game.to_notation(), position);
+        }
+        assert!(game.game_over());
+        assert!(!game.can_redo());
+    }
+
+    #[test]
+    fn scripted_dice_running_out_leaves_the_dice_unrolled() {
+        let mut game = GameState::with_dice(RuleSet::finkel(), Box::new(crate::dice::ScriptedDice::new(vec![2])));
+        assert_eq!(game.roll_dice(), Ok(2));
+        game.make_move(0).unwrap();
+        assert_eq!(game.roll_dice(), Err(MoveError::NoRollsLeft));
+        assert_eq!(game.dice_value(), 0);
+        assert_eq!(game.history().len(), 2);
+    }
+
+    #[test]
+    fn rolling_after_the_flag_falls_is_refused() {
+        let time = crate::clock::ManualClock::new();
+        let mut game = GameState::new(RuleSet::finkel());
+        game.set_time_control(TimeControl::sudden_death(1000), Box::new(time.clone()));
+
+        time.advance(1000.0);
+        assert_eq!(game.roll_dice(), Err(MoveError::OutOfTime));
+        assert!(game.game_over());
+        assert_eq!(game.winner(), Some(Player::Dark));
+        assert_eq!(game.roll_dice(), Err(MoveError::GameOver));
+        assert_eq!(game.dice_value(), 0);
+    }
+}
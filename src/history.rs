@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::Snapshot;
+use crate::moves::Move;
+use crate::Player;
+
+// One step of a game, as logged by roll_dice, make_move and pass_turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Action {
+    Roll { player: Player, value: u8 },  // A roll of 0 forfeits the turn
+    Move { mv: Move },                   // Move.captures marks captures
+    Pass { player: Player },
+}
+
+// Undo/redo log. Each entry keeps the state on the other side of its action:
+// undone entries hold the state after the action, played entries the state before it
+#[derive(Debug, Clone, Default)]
+pub(crate) struct History {
+    played: Vec<(Action, Snapshot)>,
+    undone: Vec<(Action, Snapshot)>,
+    paused: bool,  // Nothing is recorded, e.g. while an AI searches through moves
+}
+
+impl History {
+    pub(crate) fn paused() -> History {
+        History { paused: true, ..History::default() }
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn record(&mut self, action: Action, before: Snapshot) {
+        if self.paused {
+            return;
+        }
+        self.played.push((action, before));
+        // A new action replaces whatever could have been redone
+        self.undone.clear();
+    }
+
+    // Swaps the last played action's earlier state for the current one
+    pub(crate) fn undo(&mut self, current: Snapshot) -> Option<(Action, Snapshot)> {
+        let (action, before) = self.played.pop()?;
+        self.undone.push((action, current));
+        Some((action, before))
+    }
+
+    pub(crate) fn redo(&mut self, current: Snapshot) -> Option<(Action, Snapshot)> {
+        let (action, after) = self.undone.pop()?;
+        self.played.push((action, current));
+        Some((action, after))
+    }
+
+    pub(crate) fn actions(&self) -> impl Iterator<Item = &Action> {
+        self.played.iter().map(|(action, _)| action)
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.played.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+}
@@ -1,3 +1,261 @@
+// Plays two engines against each other and reports how often each wins.
+//
+//   cargo run --profile native -- [options] <player> <player>
+//
+//   Players: random, greedy, expectiminimax:<depth>, mcts:<iterations>, mcts:<millis>ms,
+//   table (needs --table) or engine:<command> for an engine speaking the protocol in ugi.rs
+//   (started once for the match and sent newgame before each game)
+//
+//   --games <n>          Games to play (100). Each seed is played twice with colours swapped
+//   --seed <n>           Dice seed of the first game (0); later games count up from it
+//   --variant <name>     Finkel (default), Masters, Blitz or Aseb
+//   --pieces <n>         Pieces per player
+//   --table <file>       Solved table for the table player
+//
+//   cargo run --profile native -- ugi
+//
+// runs this crate's engine on stdin and stdout instead
+
+use std::io;
+use std::process;
+use std::time::Instant;
+
+use game_of_ur::ai::{agent_from_spec, play_game, Agent, SolvedTable};
+use game_of_ur::{ExternalEngine, GameState, Player, RuleSet, SeededDice, UgiEngine, Variant};
+
+// z for a 95% confidence interval
+const Z: f64 = 1.96;
+
 fn main() {
-    println!("Game of Ur library - compile to WebAssembly");
+    if std::env::args().nth(1).as_deref() == Some("ugi") {
+        let stdin = io::stdin();
+        UgiEngine::new().run(stdin.lock(), io::stdout()).unwrap_or_else(|error| fail(&error.to_string()));
+        return;
+    }
+
+    let mut games = 100;
+    let mut seed = 0;
+    let mut variant = Variant::Finkel;
+    let mut pieces = None;
+    let mut table_path = None;
+    let mut players = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+        match arg.as_str() {
+            "--games" => games = parse(&value("--games"), "--games"),
+            "--seed" => seed = parse(&value("--seed"), "--seed"),
+            "--variant" => {
+                let name = value("--variant");
+                variant = Variant::from_name(&name).unwrap_or_else(|| fail(&format!("unknown variant '{}'", name)));
+            }
+            "--pieces" => pieces = Some(parse(&value("--pieces"), "--pieces")),
+            "--table" => table_path = Some(value("--table")),
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+            _ => players.push(arg),
+        }
+    }
+    if players.len() != 2 {
+        usage();
+    }
+    let mut rules = RuleSet::for_variant(variant);
+    if let Some(pieces) = pieces {
+        if !(1..=u32::from(rules.max_pieces())).contains(&pieces) {
+            fail(&format!("--pieces must be between 1 and {}", rules.max_pieces()));
+        }
+        rules.pieces = pieces as u8;
+    }
+    if players.iter().any(|spec| spec.starts_with("engine:")) && rules != RuleSet::for_variant(variant) {
+        fail("engine players only play the built-in rules, so they can't be used with --pieces");
+    }
+    let table = table_path.map(|path| {
+        SolvedTable::load(rules.clone(), &path).unwrap_or_else(|error| fail(&format!("can't load {}: {}", path, error)))
+    });
+    for spec in &players {
+        check(spec, table.is_some());
+    }
+    // Engines are started once and told about each new game, rather than started per game
+    let mut engines = [0, 1].map(|index| {
+        players[index].strip_prefix("engine:").map(|command| {
+            ExternalEngine::spawn(command).unwrap_or_else(|error| fail(&format!("can't start {}: {}", command, error)))
+        })
+    });
+
+    let mut stats = [Stats::default(), Stats::default()];
+    let mut turns = 0;
+    for game_number in 0..games {
+        // Games 2k and 2k+1 share their dice, with the players swapping colours
+        let game_seed = seed + game_number / 2;
+        let first_light = game_number % 2 == 0;
+        let (light, dark) = if first_light { (0, 1) } else { (1, 0) };
+
+        let [first_engine, second_engine] = &mut engines;
+        let mut agents = [
+            Timed::new(build(&players[0], game_seed, table.as_ref(), first_engine.as_mut(), variant)),
+            Timed::new(build(&players[1], game_seed, table.as_ref(), second_engine.as_mut(), variant)),
+        ];
+        let mut game = GameState::with_dice(rules.clone(), Box::new(SeededDice::new(game_seed)));
+        let [first, second] = &mut agents;
+        let result = if first_light { play_game(&mut game, first, second) } else { play_game(&mut game, second, first) };
+        let result = result.expect("seeded dice never run out");
+
+        turns += result.turns as u64;
+        let winner = if result.winner == Player::Light { light } else { dark };
+        for (index, agent) in agents.iter().enumerate() {
+            let colour = if index == light { Player::Light } else { Player::Dark };
+            stats[index].record(colour, index == winner, agent);
+        }
+        if (game_number + 1) % 10 == 0 || game_number + 1 == games {
+            eprint!("\r{}/{} games", game_number + 1, games);
+        }
+    }
+    eprintln!();
+
+    println!(
+        "{} vs {}: {} games of {} with {} pieces, seeds {} to {}",
+        players[0],
+        players[1],
+        games,
+        rules.variant.name(),
+        rules.pieces,
+        seed,
+        seed + games.saturating_sub(1) / 2,
+    );
+    for (spec, stats) in players.iter().zip(&stats) {
+        let (low, high) = wilson(stats.wins(), games);
+        println!(
+            "{:<20} won {:>5} ({:.1}%, 95% CI {:.1}-{:.1}%)  as Light {}/{}, as Dark {}/{}  {:.2} ms per move",
+            spec,
+            stats.wins(),
+            100.0 * stats.wins() as f64 / games.max(1) as f64,
+            100.0 * low,
+            100.0 * high,
+            stats.light_wins,
+            stats.light_games,
+            stats.dark_wins,
+            stats.dark_games,
+            stats.millis / stats.moves.max(1) as f64,
+        );
+    }
+    println!("{:.1} turns per game", turns as f64 / games.max(1) as f64);
+}
+
+// Fails unless the command-line name is a player build can make, without starting
+// anything
+fn check(spec: &str, have_table: bool) {
+    match spec.strip_prefix("engine:") {
+        Some(command) if command.trim().is_empty() => fail("engine: needs a command"),
+        Some(_) => {}
+        None if spec == "table" && !have_table => fail("the table player needs --table"),
+        None if spec == "table" || agent_from_spec(spec, 0).is_some() => {}
+        None => fail(&format!("unknown player '{}'", spec)),
+    }
+}
+
+// One player of the match from its command-line name, seeded for the given game. Engine
+// players are the already started engine, told that a new game begins
+fn build<'a>(
+    spec: &str,
+    seed: u64,
+    table: Option<&'a SolvedTable>,
+    engine: Option<&'a mut ExternalEngine>,
+    variant: Variant,
+) -> Box<dyn Agent + 'a> {
+    if let Some(engine) = engine {
+        engine.new_game(variant).unwrap_or_else(|error| fail(&format!("{}: {}", spec, error)));
+        return Box::new(engine);
+    }
+    match table {
+        Some(table) if spec == "table" => Box::new(table),
+        _ => agent_from_spec(spec, seed).expect("players are checked before the match"),
+    }
+}
+
+// Counts the time an agent spends choosing its moves
+struct Timed<'a> {
+    agent: Box<dyn Agent + 'a>,
+    moves: u32,
+    millis: f64,
+}
+
+impl<'a> Timed<'a> {
+    fn new(agent: Box<dyn Agent + 'a>) -> Self {
+        Timed { agent, moves: 0, millis: 0.0 }
+    }
+}
+
+impl Agent for Timed<'_> {
+    fn name(&self) -> String {
+        self.agent.name()
+    }
+
+    fn choose_move(&mut self, game: &GameState) -> Option<game_of_ur::Move> {
+        let started = Instant::now();
+        let choice = self.agent.choose_move(game);
+        self.millis += started.elapsed().as_secs_f64() * 1000.0;
+        self.moves += 1;
+        choice
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    light_games: u64,
+    light_wins: u64,
+    dark_games: u64,
+    dark_wins: u64,
+    moves: u64,
+    millis: f64,
+}
+
+impl Stats {
+    fn record(&mut self, colour: Player, won: bool, agent: &Timed) {
+        match colour {
+            Player::Light => {
+                self.light_games += 1;
+                self.light_wins += won as u64;
+            }
+            Player::Dark => {
+                self.dark_games += 1;
+                self.dark_wins += won as u64;
+            }
+        }
+        self.moves += agent.moves as u64;
+        self.millis += agent.millis;
+    }
+
+    fn wins(&self) -> u64 {
+        self.light_wins + self.dark_wins
+    }
+}
+
+// Wilson score interval for a win rate, which stays sensible near 0% and 100%
+fn wilson(wins: u64, games: u64) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let denominator = 1.0 + Z * Z / n;
+    let centre = (p + Z * Z / (2.0 * n)) / denominator;
+    let margin = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denominator;
+    ((centre - margin).max(0.0), (centre + margin).min(1.0))
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> T {
+    value.parse().unwrap_or_else(|_| fail(&format!("invalid value '{}' for {}", value, name)))
+}
+
+fn usage() -> ! {
+    eprintln!("usage: game-of-ur [--games <n>] [--seed <n>] [--variant <name>] [--pieces <n>] [--table <file>] <player> <player>");
+    eprintln!("       game-of-ur ugi");
+    eprintln!("players: random, greedy, expectiminimax:<depth>, mcts:<iterations>, mcts:<millis>ms, table, engine:<command>");
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("game-of-ur: {}", message);
+    process::exit(1);
 }
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Player;
+
+// A legal move for the current dice roll, described in both path and board terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub player: Player,
+    pub from: usize,                // Path position moved from, 0 = start area
+    pub to: usize,                  // Path position moved to, past the last square when bearing off
+    pub from_index: Option<usize>,  // Board index moved from, None when entering
+    pub to_index: Option<usize>,    // Board index moved to, None when bearing off
+    pub captures: bool,             // Sends an opponent piece back to start
+    pub rosette: bool,              // Lands on a rosette
+    pub bears_off: bool,            // Moves the piece off the board
+}
+
+impl Move {
+    pub fn enters(&self) -> bool {
+        self.from == 0
+    }
+}
+
+// What happened after a move was played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MoveOutcome {
+    #[serde(rename = "move")]
+    pub mv: Move,
+    pub extra_turn: bool,          // The same player rolls again
+    pub winner: Option<Player>,    // Set when the move ended the game
+}
+
+// Why a move was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveError {
+    GameOver,
+    NoDiceRolled,
+    InvalidPathPosition,   // The position isn't a square on the player's route
+    EmptySquare,
+    NotYourPiece,
+    NoPiecesInStart,
+    BlockedByOwnPiece,
+    ProtectedRosette,      // The destination is a rosette held by the opponent
+    ExactRollRequired,     // Bearing off needs the exact roll
+    OutOfTime,             // The mover's clock ran out, ending the game
+    NoRollsLeft,           // The dice source has nothing more to roll
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            MoveError::GameOver => "The game is over",
+            MoveError::NoDiceRolled => "Roll the dice first",
+            MoveError::InvalidPathPosition => "That square isn't on your path",
+            MoveError::EmptySquare => "There is no piece there",
+            MoveError::NotYourPiece => "That piece belongs to your opponent",
+            MoveError::NoPiecesInStart => "No pieces left in the start area",
+            MoveError::BlockedByOwnPiece => "Your own piece is in the way",
+            MoveError::ProtectedRosette => "A piece on a rosette can't be captured",
+            MoveError::ExactRollRequired => "You need the exact roll to bear off",
+            MoveError::OutOfTime => "Your time has run out",
+            MoveError::NoRollsLeft => "The dice have no rolls left",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn moves_describe_entering_capturing_and_rosettes() {
+        // Light on path 6 (board 5) with Dark just ahead on board 6, and on path 13 (board 15)
+        let game = GameState::from_notation(RuleSet::finkel(), "5LD8L4 5/0 6/0 L 1").unwrap();
+        let moves = game.legal_moves();
+        assert_eq!(moves.len(), 3);
+        assert!(moves[0].enters() && moves[0].from_index.is_none() && moves[0].to_index == Some(3));
+        assert_eq!((moves[1].from_index, moves[1].to_index, moves[1].captures), (Some(5), Some(6), true));
+        assert_eq!((moves[2].to, moves[2].to_index, moves[2].bears_off), (14, Some(14), false));
+        assert!(moves[2].rosette);
+    }
+
+    #[test]
+    fn bearing_off_needs_the_exact_roll() {
+        // Light on path 13 (board 15); the route ends after path 14, so 2 bears off exactly
+        let exact = GameState::from_notation(RuleSet::finkel(), "15L4 6/0 7/0 L 2").unwrap();
+        let mv = exact.check_move(13).unwrap();
+        assert!(mv.bears_off && mv.to == 15 && mv.to_index.is_none() && !mv.captures);
+
+        let over = GameState::from_notation(RuleSet::finkel(), "15L4 6/0 7/0 L 3").unwrap();
+        assert_eq!(over.check_move(13), Err(MoveError::ExactRollRequired));
+
+        let mut rules = RuleSet::finkel();
+        rules.exact_bear_off = false;
+        let over = GameState::from_notation(rules, "15L4 6/0 7/0 L 3").unwrap();
+        assert!(over.check_move(13).unwrap().bears_off);
+    }
+
+    #[test]
+    fn rejected_moves_say_why() {
+        let unrolled = GameState::new(RuleSet::finkel());
+        assert_eq!(unrolled.check_move(0), Err(MoveError::NoDiceRolled));
+
+        // Light on board 2 and 1 (paths 2 and 3), Dark on board 10 and the central rosette
+        let game = GameState::from_notation(RuleSet::finkel(), "1LL4D2D9 0/5 5/0 L 1").unwrap();
+        assert_eq!(game.check_move(0), Err(MoveError::NoPiecesInStart));
+        assert_eq!(game.check_move(2), Err(MoveError::BlockedByOwnPiece));
+        assert_eq!(game.check_move(4), Err(MoveError::EmptySquare));
+        assert_eq!(game.check_move(15), Err(MoveError::InvalidPathPosition));
+        let dark_piece = game.board_index_to_path(7, Player::Light).unwrap();
+        assert_eq!(game.check_move(dark_piece), Err(MoveError::NotYourPiece));
+
+        let game = GameState::from_notation(RuleSet::finkel(), "5LLD12 5/0 6/0 L 2").unwrap();
+        assert_eq!(game.check_move(6), Err(MoveError::ProtectedRosette));
+        let game = GameState::from_notation(RuleSet::finkel(), "15L4 6/0 7/0 L 3").unwrap();
+        assert_eq!(game.check_move(13), Err(MoveError::ExactRollRequired));
+
+        let won = GameState::from_notation(RuleSet::finkel(), "20 0/7 7/0 D -").unwrap();
+        assert_eq!(won.check_move(0), Err(MoveError::GameOver));
+
+        // Every error explains itself differently
+        let errors = [
+            MoveError::GameOver,
+            MoveError::NoDiceRolled,
+            MoveError::InvalidPathPosition,
+            MoveError::EmptySquare,
+            MoveError::NotYourPiece,
+            MoveError::NoPiecesInStart,
+            MoveError::BlockedByOwnPiece,
+            MoveError::ProtectedRosette,
+            MoveError::ExactRollRequired,
+            MoveError::OutOfTime,
+            MoveError::NoRollsLeft,
+        ];
+        let messages: std::collections::HashSet<String> = errors.iter().map(MoveError::to_string).collect();
+        assert_eq!(messages.len(), errors.len());
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::game::{GameState, Player};
+use crate::rules::RuleSet;
+
+// Position notation, five space-separated fields:
+//   squares  each square by board index: L or D for a piece, a number for a run of empty squares
+//   light    Light's pieces in start / pieces off
+//   dark     Dark's pieces in start / pieces off
+//   turn     side to move, L or D
+//   dice     pending roll, or - before rolling
+// The opening position of a 20-square, 7-piece game is "20 7/0 7/0 L -"
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    WrongFieldCount { found: usize },
+    InvalidSquare { found: char },
+    WrongSquareCount { expected: usize, found: usize },
+    InvalidCounts { field: String },             // Not written as start/off
+    PieceCountMismatch { player: Player },       // Start, board and off don't add up to the rules' pieces
+    InvalidTurn { field: String },
+    InvalidDice { field: String },
+    BothPlayersWon,
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::WrongFieldCount { found } => write!(f, "expected 5 fields, found {}", found),
+            NotationError::InvalidSquare { found } => write!(f, "'{}' is not a piece or a run of empty squares", found),
+            NotationError::WrongSquareCount { expected, found } => {
+                write!(f, "expected {} squares, found {}", expected, found)
+            }
+            NotationError::InvalidCounts { field } => write!(f, "'{}' is not start/off piece counts", field),
+            NotationError::PieceCountMismatch { player } => {
+                write!(f, "{}'s pieces don't add up to the number in play", player.name())
+            }
+            NotationError::InvalidTurn { field } => write!(f, "'{}' is not L or D", field),
+            NotationError::InvalidDice { field } => write!(f, "'{}' is not a roll of these dice", field),
+            NotationError::BothPlayersWon => write!(f, "both players have borne off every piece"),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl GameState {
+    pub fn to_notation(&self) -> String {
+        let mut squares = String::new();
+        let mut empty = 0;
+        for square in self.board() {
+            match square.piece {
+                None => empty += 1,
+                Some(player) => {
+                    if empty > 0 {
+                        squares.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    squares.push(player_letter(player));
+                }
+            }
+        }
+        if empty > 0 {
+            squares.push_str(&empty.to_string());
+        }
+
+        let dice = match self.dice_value() {
+            0 => "-".to_string(),
+            value => value.to_string(),
+        };
+        format!(
+            "{} {}/{} {}/{} {} {}",
+            squares,
+            self.light_pieces_start(),
+            self.light_pieces_off(),
+            self.dark_pieces_start(),
+            self.dark_pieces_off(),
+            player_letter(self.current_player()),
+            dice,
+        )
+    }
+
+    // Loads a position written by to_notation, with a fresh history and dice source
+    pub fn from_notation(rules: RuleSet, notation: &str) -> Result<GameState, NotationError> {
+        let fields: Vec<&str> = notation.split_whitespace().collect();
+        let [squares, light, dark, turn, dice] = fields[..] else {
+            return Err(NotationError::WrongFieldCount { found: fields.len() });
+        };
+
+        let pieces = parse_squares(squares, rules.layout().len())?;
+        let (light_start, light_off) = parse_counts(light)?;
+        let (dark_start, dark_off) = parse_counts(dark)?;
+        for (player, start, off) in [(Player::Light, light_start, light_off), (Player::Dark, dark_start, dark_off)] {
+            let on_board = pieces.iter().filter(|&&piece| piece == Some(player)).count();
+            if start as usize + on_board + off as usize != rules.pieces as usize {
+                return Err(NotationError::PieceCountMismatch { player });
+            }
+        }
+        if light_off == rules.pieces && dark_off == rules.pieces {
+            return Err(NotationError::BothPlayersWon);
+        }
+
+        let current_player = match turn {
+            "L" => Player::Light,
+            "D" => Player::Dark,
+            _ => return Err(NotationError::InvalidTurn { field: turn.to_string() }),
+        };
+        // A roll of 0 passes the turn at once, so a pending roll is never 0
+        let dice_value = match dice {
+            "-" => 0,
+            _ => match dice.parse::<u8>() {
+                Ok(value) if (1..=rules.dice.max_value()).contains(&value) => value,
+                _ => return Err(NotationError::InvalidDice { field: dice.to_string() }),
+            },
+        };
+
+        Ok(GameState::from_parts(rules, &pieces, light_off, dark_off, current_player, dice_value))
+    }
+}
+
+fn player_letter(player: Player) -> char {
+    match player {
+        Player::Light => 'L',
+        Player::Dark => 'D',
+    }
+}
+
+fn parse_squares(field: &str, expected: usize) -> Result<Vec<Option<Player>>, NotationError> {
+    // Runs can be more than one digit long, e.g. 20 for an empty board
+    let mut tokens = Vec::new();
+    let mut run: Option<usize> = None;
+    for c in field.chars() {
+        match c {
+            '0'..='9' => run = Some(run.unwrap_or(0).saturating_mul(10).saturating_add(c as usize - '0' as usize)),
+            'L' | 'D' => {
+                if let Some(empty) = run.take() {
+                    tokens.push((empty, None));
+                }
+                tokens.push((1, Some(if c == 'L' { Player::Light } else { Player::Dark })));
+            }
+            _ => return Err(NotationError::InvalidSquare { found: c }),
+        }
+    }
+    if let Some(empty) = run {
+        tokens.push((empty, None));
+    }
+
+    let found = tokens.iter().fold(0usize, |total, &(count, _)| total.saturating_add(count));
+    if found != expected {
+        return Err(NotationError::WrongSquareCount { expected, found });
+    }
+    Ok(tokens.into_iter().flat_map(|(count, piece)| std::iter::repeat_n(piece, count)).collect())
+}
+
+fn parse_counts(field: &str) -> Result<(u8, u8), NotationError> {
+    let invalid = || NotationError::InvalidCounts { field: field.to_string() };
+    let (start, off) = field.split_once('/').ok_or_else(invalid)?;
+    Ok((start.parse().map_err(|_| invalid())?, off.parse().map_err(|_| invalid())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::SeededDice;
+
+    #[test]
+    fn notation_reads_back_to_the_same_position() {
+        for rules in [RuleSet::finkel(), RuleSet::masters(), RuleSet::aseb()] {
+            let mut game = GameState::with_dice(rules.clone(), Box::new(SeededDice::new(3)));
+            while !game.game_over() {
+                let notation = game.to_notation();
+                let read = GameState::from_notation(rules.clone(), &notation).unwrap();
+                assert_eq!(read.to_notation(), notation);
+                assert_eq!(read.position_key(), game.position_key());
+
+                if game.dice_value() == 0 {
+                    game.roll_dice().unwrap();
+                } else if let Some(mv) = game.legal_moves().last() {
+                    game.make_move(mv.from).unwrap();
+                } else {
+                    game.pass_turn();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+
+use crate::dice::{splitmix64, GOLDEN_GAMMA};
+use crate::game::{GameState, Player};
+use crate::rules::RuleSet;
+
+// Keys and hashes describe a position under one rule set: the same key can mean different
+// positions in different variants. History, dice source and rule set aren't part of them
+
+// Salt for the Zobrist feature values, so they don't line up with a dice stream seeded at 0
+const ZOBRIST_SALT: u64 = 0x5552_5A4F_4252_4953;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    TooManyPositions,          // The rule set's positions don't fit in a u64 key
+    KeyOutOfRange,
+    TooManyPieces { player: Player },
+    BothPlayersWon,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::TooManyPositions => write!(f, "positions of these rules don't fit in a key"),
+            PositionError::KeyOutOfRange => write!(f, "key is past the last position"),
+            PositionError::TooManyPieces { player } => write!(f, "{} has more pieces than the rules allow", player.name()),
+            PositionError::BothPlayersWon => write!(f, "both players have borne off every piece"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl GameState {
+    // Mixed-radix key of the position, from least to most significant digit: dice value,
+    // side to move, Light pieces off, Dark pieces off, then each square (0 empty, 1 Light,
+    // 2 Dark) by board index. Start counts follow from the rest, so every key below
+    // RuleSet::position_count names at most one position. Fails for rule sets whose
+    // position_count is None; every built-in variant fits
+    pub fn position_key(&self) -> Result<u64, PositionError> {
+        let rules = self.rules();
+        rules.position_count().ok_or(PositionError::TooManyPositions)?;
+
+        let mut key = 0;
+        for square in self.board().iter().rev() {
+            key = key * 3 + piece_digit(square.piece);
+        }
+        let counts = u64::from(rules.pieces) + 1;
+        key = key * counts + u64::from(self.dark_pieces_off());
+        key = key * counts + u64::from(self.light_pieces_off());
+        key = key * 2 + player_digit(self.current_player());
+        Ok(key * (u64::from(rules.dice.max_value()) + 1) + u64::from(self.dice_value()))
+    }
+
+    // Rebuilds the position of position_key, with a fresh history and dice source
+    pub fn from_position_key(rules: RuleSet, key: u64) -> Result<GameState, PositionError> {
+        let count = rules.position_count().ok_or(PositionError::TooManyPositions)?;
+        if key >= count {
+            return Err(PositionError::KeyOutOfRange);
+        }
+
+        let mut rest = key;
+        let mut digit = |radix: u64| {
+            let value = rest % radix;
+            rest /= radix;
+            value
+        };
+        let dice_value = digit(u64::from(rules.dice.max_value()) + 1) as u8;
+        let current_player = if digit(2) == 0 { Player::Light } else { Player::Dark };
+        let counts = u64::from(rules.pieces) + 1;
+        let light_off = digit(counts) as u8;
+        let dark_off = digit(counts) as u8;
+        let pieces: Vec<Option<Player>> = (0..rules.layout().len())
+            .map(|_| match digit(3) {
+                1 => Some(Player::Light),
+                2 => Some(Player::Dark),
+                _ => None,
+            })
+            .collect();
+
+        for (player, off) in [(Player::Light, light_off), (Player::Dark, dark_off)] {
+            let on_board = pieces.iter().filter(|&&piece| piece == Some(player)).count();
+            if on_board + off as usize > rules.pieces as usize {
+                return Err(PositionError::TooManyPieces { player });
+            }
+        }
+        if light_off == rules.pieces && dark_off == rules.pieces {
+            return Err(PositionError::BothPlayersWon);
+        }
+
+        Ok(GameState::from_parts(rules, &pieces, light_off, dark_off, current_player, dice_value))
+    }
+
+    // Zobrist hash of the same position as position_key: the XOR of a fixed random value
+    // for each piece on a square, each player's pieces-off count, the side to move and the
+    // dice value. Unlike the key it works for boards of any size, but may collide
+    pub fn zobrist_hash(&self) -> u64 {
+        let squares = self.board().len() as u64;
+        let mut hash = 0;
+        for (index, square) in self.board().iter().enumerate() {
+            if let Some(player) = square.piece {
+                hash ^= zobrist(index as u64 * 2 + player_digit(player));
+            }
+        }
+        // Pieces-off counts take 256 features per player, the side to move and dice follow
+        let off_features = squares * 2;
+        hash ^= zobrist(off_features + u64::from(self.light_pieces_off()));
+        hash ^= zobrist(off_features + 256 + u64::from(self.dark_pieces_off()));
+        let turn_features = off_features + 512;
+        if self.current_player() == Player::Dark {
+            hash ^= zobrist(turn_features);
+        }
+        hash ^ zobrist(turn_features + 1 + u64::from(self.dice_value()))
+    }
+}
+
+fn zobrist(feature: u64) -> u64 {
+    splitmix64(ZOBRIST_SALT.wrapping_add(feature.wrapping_mul(GOLDEN_GAMMA)))
+}
+
+fn piece_digit(piece: Option<Player>) -> u64 {
+    match piece {
+        None => 0,
+        Some(Player::Light) => 1,
+        Some(Player::Dark) => 2,
+    }
+}
+
+fn player_digit(player: Player) -> u64 {
+    match player {
+        Player::Light => 0,
+        Player::Dark => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BoardLayout, Cell};
+    use crate::dice::SeededDice;
+    use crate::rules::Variant;
+
+    #[test]
+    fn position_keys_rebuild_the_same_position() {
+        let rules = RuleSet::masters();
+        let mut game = GameState::with_dice(rules.clone(), Box::new(SeededDice::new(5)));
+        while !game.game_over() {
+            let key = game.position_key().unwrap();
+            let rebuilt = GameState::from_position_key(rules.clone(), key).unwrap();
+            assert_eq!(rebuilt.position_key(), Ok(key));
+            assert_eq!(rebuilt.to_notation(), game.to_notation());
+
+            if game.dice_value() == 0 {
+                game.roll_dice().unwrap();
+            } else if let Some(mv) = game.legal_moves().first() {
+                game.make_move(mv.from).unwrap();
+            } else {
+                game.pass_turn();
+            }
+        }
+        let count = rules.position_count().unwrap();
+        assert_eq!(GameState::from_position_key(rules, count).err(), Some(PositionError::KeyOutOfRange));
+    }
+
+    #[test]
+    fn rule_sets_too_big_for_a_key_are_refused() {
+        let cells = (0..3).flat_map(|row| (0..16).map(move |col| Cell::new(row, col))).collect();
+        let layout = BoardLayout::new(cells).unwrap();
+        let rules = RuleSet::with_board(Variant::Finkel, layout, (0..16).collect(), (16..32).collect()).unwrap();
+        assert_eq!(rules.position_count(), None);
+        let game = GameState::new(rules);
+        assert_eq!(game.position_key(), Err(PositionError::TooManyPositions));
+    }
+}
@@ -0,0 +1,561 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dice::{ScriptedDice, SeededDice};
+use crate::game::{GameState, Player, Termination};
+use crate::history::Action;
+use crate::moves::MoveError;
+use crate::notation::NotationError;
+use crate::rules::{RuleSet, Variant};
+
+// Game record in a PGN-like text format: tag lines, a blank line, then the movetext.
+//
+//   [Variant "Finkel"]
+//   [Light "Ann"]
+//   [Dark "Bob"]
+//   [Date "2026.10.18"]
+//   [Seed "9"]
+//   [Result "*"]
+//
+//   L1 0-1 D1 0-1 L2 1-3 D0 L1 3-4* L3 4-7 D3 1-4* D3 4-7x L2 0-2
+//
+// Each turn starts with the player and their roll (L3: Light rolled 3), followed by the
+// move as path positions from-to, or pass. A roll of 0 ends the turn by itself. Moves are
+// marked x for a capture and * for landing on a rosette; bearing off moves to the position
+// past the last square. Text in {braces} is a comment on the action before it.
+//
+// Seed is the dice stream when the game started, as seed or seed:position, so replays can
+// check every roll. Position holds the starting position in notation when it isn't the
+// opening. Result is 1-0 when Light won, 0-1 when Dark won and * while the game goes on;
+// unlike PGN it only appears as a tag, since 0-1 is also a move. Termination is "time
+// forfeit" or "abandoned" when the loser ran out of time or forfeited after the last action
+
+// One roll, move or pass as written in the movetext
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedAction {
+    Roll { player: Player, value: u8 },
+    Move { from: usize, to: usize, captures: bool, rosette: bool },
+    Pass,
+}
+
+impl From<Action> for RecordedAction {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Roll { player, value } => RecordedAction::Roll { player, value },
+            Action::Move { mv } => RecordedAction::Move {
+                from: mv.from,
+                to: mv.to,
+                captures: mv.captures,
+                rosette: mv.rosette,
+            },
+            Action::Pass { .. } => RecordedAction::Pass,
+        }
+    }
+}
+
+impl fmt::Display for RecordedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordedAction::Roll { player, value } => write!(f, "{}{}", player_letter(*player), value),
+            RecordedAction::Move { from, to, captures, rosette } => {
+                write!(f, "{}-{}", from, to)?;
+                if *captures {
+                    write!(f, "x")?;
+                }
+                if *rosette {
+                    write!(f, "*")?;
+                }
+                Ok(())
+            }
+            RecordedAction::Pass => write!(f, "pass"),
+        }
+    }
+}
+
+// One movetext token: L3, 0-3x*, pass
+impl FromStr for RecordedAction {
+    type Err = RecordError;
+
+    fn from_str(token: &str) -> Result<RecordedAction, RecordError> {
+        parse_action(token).ok_or_else(|| RecordError::InvalidToken { token: token.to_string() })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub variant: Variant,
+    pub light: Option<String>,         // Player names
+    pub dark: Option<String>,
+    pub date: Option<String>,
+    pub seed: Option<SeededDice>,      // Dice stream at the start, if the rolls can be reproduced
+    pub position: Option<String>,      // Starting position in notation, None for the opening
+    pub result: Option<Player>,
+    pub termination: Option<Termination>, // None if the game was won on the board, or goes on
+    pub tags: Vec<(String, String)>,   // Any other tags, kept in order
+    pub actions: Vec<RecordedAction>,
+    pub comments: Vec<(usize, String)>, // Each comment with the number of actions before it
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    // Reading the text
+    InvalidTag { line: String },
+    MissingVariant,
+    UnknownVariant { name: String },
+    InvalidSeed { value: String },
+    InvalidResult { value: String },
+    InvalidTermination { value: String },
+    UnclosedComment,
+    InvalidToken { token: String },
+    // Replaying the actions, with the index of the action that failed
+    InvalidPosition(NotationError),
+    RollOutOfTurn { index: usize },                   // Wrong player, or the dice were already rolled
+    ImpossibleRoll { index: usize, value: u8 },       // Not a value the dice can show
+    RollMismatch { index: usize, recorded: u8, rolled: u8 },
+    IllegalMove { index: usize, error: MoveError },
+    MoveMismatch { index: usize },                    // The move lands elsewhere than recorded
+    PassWithoutRoll { index: usize },
+    ResultMismatch,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::InvalidTag { line } => write!(f, "'{}' is not a tag", line),
+            RecordError::MissingVariant => write!(f, "record has no Variant tag"),
+            RecordError::UnknownVariant { name } => write!(f, "unknown variant '{}'", name),
+            RecordError::InvalidSeed { value } => write!(f, "'{}' is not a dice seed", value),
+            RecordError::InvalidResult { value } => write!(f, "'{}' is not a result", value),
+            RecordError::InvalidTermination { value } => write!(f, "'{}' is not a termination", value),
+            RecordError::UnclosedComment => write!(f, "comment is missing its closing brace"),
+            RecordError::InvalidToken { token } => write!(f, "'{}' is not a roll, move or pass", token),
+            RecordError::InvalidPosition(error) => write!(f, "invalid starting position: {}", error),
+            RecordError::RollOutOfTurn { index } => write!(f, "action {}: roll out of turn", index + 1),
+            RecordError::ImpossibleRoll { index, value } => {
+                write!(f, "action {}: the dice can't roll {}", index + 1, value)
+            }
+            RecordError::RollMismatch { index, recorded, rolled } => {
+                write!(f, "action {}: recorded a roll of {} but the seed rolls {}", index + 1, recorded, rolled)
+            }
+            RecordError::IllegalMove { index, error } => write!(f, "action {}: {}", index + 1, error),
+            RecordError::MoveMismatch { index } => {
+                write!(f, "action {}: the move doesn't match the position", index + 1)
+            }
+            RecordError::PassWithoutRoll { index } => write!(f, "action {}: pass before rolling", index + 1),
+            RecordError::ResultMismatch => write!(f, "the replayed game doesn't end with the recorded result"),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<NotationError> for RecordError {
+    fn from(error: NotationError) -> Self {
+        RecordError::InvalidPosition(error)
+    }
+}
+
+impl GameRecord {
+    // Plays the record from its starting position under the variant's rules
+    pub fn replay(&self) -> Result<GameState, RecordError> {
+        self.replay_with(RuleSet::for_variant(self.variant))
+    }
+
+    // Plays the record under the given rules, checking every roll and move as it goes.
+    // Without a seed the recorded rolls are taken as they are; either way the returned
+    // game can carry on from where the record stops
+    pub fn replay_with(&self, rules: RuleSet) -> Result<GameState, RecordError> {
+        let mut game = match &self.position {
+            Some(notation) => GameState::from_notation(rules, notation)?,
+            None => GameState::new(rules),
+        };
+        let recorded_rolls: Vec<u8> = self.actions.iter().filter_map(|action| match action {
+            RecordedAction::Roll { value, .. } => Some(*value),
+            _ => None,
+        }).collect();
+        match self.seed {
+            Some(seed) => game.set_dice_source(Box::new(seed)),
+            None => game.set_dice_source(Box::new(ScriptedDice::new(recorded_rolls))),
+        }
+
+        for (index, action) in self.actions.iter().enumerate() {
+            match *action {
+                RecordedAction::Roll { player, value } => {
+                    if game.game_over() || game.dice_value() != 0 || game.current_player() != player {
+                        return Err(RecordError::RollOutOfTurn { index });
+                    }
+                    if !game.rules().dice.can_roll(value) {
+                        return Err(RecordError::ImpossibleRoll { index, value });
+                    }
+                    let rolled = game.roll_dice().map_err(|error| RecordError::IllegalMove { index, error })?;
+                    if rolled != value {
+                        return Err(RecordError::RollMismatch { index, recorded: value, rolled });
+                    }
+                }
+                RecordedAction::Move { from, to, captures, rosette } => {
+                    let mv = game.check_move(from).map_err(|error| RecordError::IllegalMove { index, error })?;
+                    if (mv.to, mv.captures, mv.rosette) != (to, captures, rosette) {
+                        return Err(RecordError::MoveMismatch { index });
+                    }
+                    game.make_move(from).map_err(|error| RecordError::IllegalMove { index, error })?;
+                }
+                RecordedAction::Pass => {
+                    if game.dice_value() == 0 {
+                        return Err(RecordError::PassWithoutRoll { index });
+                    }
+                    game.pass_turn();
+                }
+            }
+        }
+
+        // A game lost on time or by forfeit ends after its last action, against the loser
+        if let Some(termination) = self.termination {
+            match self.result {
+                Some(winner) if !game.game_over() => game.lose(winner.opponent(), termination),
+                _ => return Err(RecordError::ResultMismatch),
+            }
+        }
+        if self.result.is_some() && self.result != game.winner() {
+            return Err(RecordError::ResultMismatch);
+        }
+        if self.seed.is_none() {
+            // The scripted rolls are used up, so carry on with fresh dice
+            game.set_dice_source(Box::new(SeededDice::from_entropy()));
+        }
+        Ok(game)
+    }
+}
+
+impl GameState {
+    // Record of the game so far, from the position before the first action in the history.
+    // Player names and date are left for the caller to fill in
+    pub fn record(&self) -> GameRecord {
+        let mut start = self.clone();
+        while start.undo().is_some() {}
+        let opening = GameState::with_dice(self.rules().clone(), start.dice_source().clone_box());
+        let position = start.to_notation();
+
+        GameRecord {
+            variant: self.rules().variant,
+            light: None,
+            dark: None,
+            date: None,
+            seed: start.dice_source().seed(),
+            position: (position != opening.to_notation()).then_some(position),
+            result: self.winner(),
+            termination: self.termination(),
+            tags: Vec::new(),
+            actions: self.history().into_iter().map(RecordedAction::from).collect(),
+            comments: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tag(f, "Variant", self.variant.name())?;
+        for (name, value) in [("Light", &self.light), ("Dark", &self.dark), ("Date", &self.date)] {
+            if let Some(value) = value {
+                write_tag(f, name, value)?;
+            }
+        }
+        if let Some(seed) = self.seed {
+            let value = match seed.position {
+                0 => seed.seed.to_string(),
+                position => format!("{}:{}", seed.seed, position),
+            };
+            write_tag(f, "Seed", &value)?;
+        }
+        if let Some(position) = &self.position {
+            write_tag(f, "Position", position)?;
+        }
+        let result = match self.result {
+            Some(Player::Light) => "1-0",
+            Some(Player::Dark) => "0-1",
+            None => "*",
+        };
+        write_tag(f, "Result", result)?;
+        if let Some(termination) = self.termination {
+            write_tag(f, "Termination", termination_name(termination))?;
+        }
+        for (name, value) in &self.tags {
+            write_tag(f, name, value)?;
+        }
+        writeln!(f)?;
+
+        // Movetext, wrapped at 80 columns like PGN
+        let mut line = String::new();
+        let mut comments = self.comments.iter().peekable();
+        let mut tokens = Vec::new();
+        for (index, action) in self.actions.iter().enumerate() {
+            while let Some((_, comment)) = comments.next_if(|(after, _)| *after <= index) {
+                tokens.push(format!("{{{}}}", comment));
+            }
+            tokens.push(action.to_string());
+        }
+        tokens.extend(comments.map(|(_, comment)| format!("{{{}}}", comment)));
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        if !line.is_empty() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(text: &str) -> Result<GameRecord, RecordError> {
+        let mut variant = None;
+        let mut record = GameRecord {
+            variant: Variant::Finkel,
+            light: None,
+            dark: None,
+            date: None,
+            seed: None,
+            position: None,
+            result: None,
+            termination: None,
+            tags: Vec::new(),
+            actions: Vec::new(),
+            comments: Vec::new(),
+        };
+
+        // Tags run until the first line that isn't one
+        let mut lines = text.lines().peekable();
+        while let Some(line) = lines.next_if(|line| line.trim().is_empty() || line.trim_start().starts_with('[')) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = parse_tag(line).ok_or_else(|| RecordError::InvalidTag { line: line.to_string() })?;
+            match name.as_str() {
+                "Variant" => {
+                    variant = Some(Variant::from_name(&value).ok_or(RecordError::UnknownVariant { name: value })?)
+                }
+                "Light" => record.light = Some(value),
+                "Dark" => record.dark = Some(value),
+                "Date" => record.date = Some(value),
+                "Seed" => record.seed = Some(parse_seed(&value).ok_or(RecordError::InvalidSeed { value })?),
+                "Position" => record.position = Some(value),
+                "Result" => {
+                    record.result = match value.as_str() {
+                        "1-0" => Some(Player::Light),
+                        "0-1" => Some(Player::Dark),
+                        "*" => None,
+                        _ => return Err(RecordError::InvalidResult { value }),
+                    }
+                }
+                "Termination" => {
+                    record.termination = match value.as_str() {
+                        "time forfeit" => Some(Termination::TimeForfeit),
+                        "abandoned" => Some(Termination::Abandoned),
+                        "normal" => None,
+                        _ => return Err(RecordError::InvalidTermination { value }),
+                    }
+                }
+                _ => record.tags.push((name, value)),
+            }
+        }
+        record.variant = variant.ok_or(RecordError::MissingVariant)?;
+
+        let movetext: Vec<&str> = lines.collect();
+        let movetext = movetext.join("\n");
+        let mut rest = movetext.as_str();
+        loop {
+            let (text, comment) = match rest.find('{') {
+                Some(open) => {
+                    let close = rest[open..].find('}').ok_or(RecordError::UnclosedComment)?;
+                    let comment = rest[open + 1..open + close].split_whitespace().collect::<Vec<_>>().join(" ");
+                    let text = &rest[..open];
+                    rest = &rest[open + close + 1..];
+                    (text, Some(comment))
+                }
+                None => (std::mem::take(&mut rest), None),
+            };
+            for token in text.split_whitespace() {
+                record.actions.push(token.parse()?);
+            }
+            match comment {
+                Some(comment) => record.comments.push((record.actions.len(), comment)),
+                None => break,
+            }
+        }
+        Ok(record)
+    }
+}
+
+fn player_letter(player: Player) -> char {
+    match player {
+        Player::Light => 'L',
+        Player::Dark => 'D',
+    }
+}
+
+fn termination_name(termination: Termination) -> &'static str {
+    match termination {
+        Termination::TimeForfeit => "time forfeit",
+        Termination::Abandoned => "abandoned",
+    }
+}
+
+fn write_tag(f: &mut fmt::Formatter<'_>, name: &str, value: &str) -> fmt::Result {
+    writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// [Name "value"], with \" and \\ escapes inside the value
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, quoted) = inner.split_once(char::is_whitespace)?;
+    let quoted = quoted.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?),
+            '"' => return None,
+            _ => value.push(c),
+        }
+    }
+    Some((name.to_string(), value))
+}
+
+// seed or seed:position
+fn parse_seed(value: &str) -> Option<SeededDice> {
+    let (seed, position) = value.split_once(':').unwrap_or((value, "0"));
+    Some(SeededDice { seed: seed.parse().ok()?, position: position.parse().ok()? })
+}
+
+fn parse_action(token: &str) -> Option<RecordedAction> {
+    if token == "pass" {
+        return Some(RecordedAction::Pass);
+    }
+    if let Some((from, to)) = token.split_once('-') {
+        let rosette = to.ends_with('*');
+        let to = to.trim_end_matches('*');
+        let captures = to.ends_with('x');
+        let to = to.trim_end_matches('x');
+        return Some(RecordedAction::Move { from: from.parse().ok()?, to: to.parse().ok()?, captures, rosette });
+    }
+    let player = match token.chars().next()? {
+        'L' => Player::Light,
+        'D' => Player::Dark,
+        _ => return None,
+    };
+    Some(RecordedAction::Roll { player, value: token[1..].parse().ok()? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{play_game, RandomAgent};
+    use crate::clock::{ManualClock, TimeControl};
+
+    fn played_game(seed: u64) -> GameState {
+        let mut game = GameState::with_dice(RuleSet::finkel(), Box::new(SeededDice::new(seed)));
+        play_game(&mut game, &mut RandomAgent::new(1), &mut RandomAgent::new(2)).unwrap();
+        game
+    }
+
+    #[test]
+    fn records_read_back_and_replay_to_the_same_game() {
+        let game = played_game(11);
+        let text = game.record().to_string();
+        let record: GameRecord = text.parse().unwrap();
+        assert_eq!(record.to_string(), text);
+
+        let replayed = record.replay().unwrap();
+        assert_eq!(replayed.to_notation(), game.to_notation());
+        assert_eq!(replayed.history(), game.history());
+        assert_eq!(replayed.winner(), game.winner());
+    }
+
+    #[test]
+    fn records_without_a_seed_replay_their_rolls() {
+        let game = played_game(12);
+        let mut record = game.record();
+        record.seed = None;
+        let replayed = record.replay().unwrap();
+        assert_eq!(replayed.to_notation(), game.to_notation());
+    }
+
+    // The example at the top of this file, so it can't drift from the format
+    #[test]
+    fn the_example_record_replays() {
+        let example: Vec<&str> = include_str!("record.rs")
+            .lines()
+            .skip_while(|line| !line.starts_with("//   [Variant"))
+            .take_while(|line| line.starts_with("//   ") || *line == "//")
+            .map(|line| line.trim_start_matches("//").trim())
+            .collect();
+        let record: GameRecord = example.join("\n").parse().unwrap();
+        assert_eq!(record.actions.len(), 17);
+
+        let replayed = record.replay().unwrap();
+        assert_eq!(replayed.record().actions, record.actions);
+        assert!(!replayed.game_over());
+    }
+
+    #[test]
+    fn replay_rejects_a_roll_the_seed_did_not_make() {
+        let mut record = played_game(13).record();
+        let roll = record.actions.iter_mut().find_map(|action| match action {
+            RecordedAction::Roll { value, .. } => Some(value),
+            _ => None,
+        });
+        let value = roll.unwrap();
+        *value = (*value + 1) % 5;
+        assert!(matches!(record.replay(), Err(RecordError::RollMismatch { index: 0, .. })));
+    }
+
+    #[test]
+    fn replay_rejects_a_roll_the_dice_can_not_show() {
+        let text = "[Variant \"Masters\"]\n\nL0";
+        let record: GameRecord = text.parse().unwrap();
+        assert_eq!(record.replay().err(), Some(RecordError::ImpossibleRoll { index: 0, value: 0 }));
+    }
+
+    #[test]
+    fn games_lost_on_time_replay_to_the_same_result() {
+        let time = ManualClock::new();
+        let mut game = GameState::with_dice(RuleSet::finkel(), Box::new(SeededDice::new(14)));
+        game.set_time_control(TimeControl::sudden_death(1000), Box::new(time.clone()));
+        game.roll_dice().unwrap();
+        time.advance(1000.0);
+        assert!(game.check_time());
+
+        let text = game.record().to_string();
+        assert!(text.contains("[Termination \"time forfeit\"]"));
+        let replayed = text.parse::<GameRecord>().unwrap().replay().unwrap();
+        assert_eq!(replayed.winner(), game.winner());
+        assert_eq!(replayed.termination(), Some(Termination::TimeForfeit));
+        assert_eq!(replayed.record().to_string(), text);
+    }
+
+    #[test]
+    fn forfeited_games_replay_to_the_same_result() {
+        let mut game = GameState::with_dice(RuleSet::finkel(), Box::new(SeededDice::new(15)));
+        game.roll_dice().unwrap();
+        game.forfeit(Player::Dark);
+
+        let record = game.record();
+        assert_eq!(record.termination, Some(Termination::Abandoned));
+        let replayed = record.replay().unwrap();
+        assert_eq!(replayed.winner(), Some(Player::Light));
+        assert_eq!(replayed.termination(), Some(Termination::Abandoned));
+
+        let mut unfinished = record.clone();
+        unfinished.result = None;
+        assert_eq!(unfinished.replay().err(), Some(RecordError::ResultMismatch));
+    }
+}
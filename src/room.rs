@@ -0,0 +1,694 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{SystemClock, TimeControl, TimeSource};
+use crate::dice::ScriptedDice;
+use crate::events::GameEvent;
+use crate::fair_dice::{Commitment, FairRoll, Secret};
+use crate::game::{GameState, Player};
+use crate::rules::{RuleSet, Variant};
+
+// Hosted games for the WebSocket server, kept free of networking so the rules of the
+// protocol can be used and checked on their own. Messages are JSON objects tagged by type.
+//
+// Client to server:
+//   { "type": "join", "room": "club", "name": "Ann", "variant": "Finkel",
+//     "time_control": { "initial_ms": 180000, "increment_ms": 2000, "delay_ms": 0 } }
+//                    Takes the first free seat, creating the room if needed. Name, variant
+//                    and time control are optional. Whoever sits down in an empty room sets
+//                    up a new game of the variant (Finkel by default), untimed unless a time
+//                    control is given; a later join asking for other settings is refused.
+//                    Clocks start once both seats are taken. A player who leaves once the
+//                    first roll is made forfeits the game, so nobody can take over their side
+//   { "type": "watch", "room": "club", "delay": 30 }
+//                    Follows the room without playing, everything arriving delay seconds
+//                    late (none by default). Creates the room if needed, like join
+//   { "type": "roll" }                   Starts a fair roll (see fair_dice.rs)
+//   { "type": "commit", "commitment": "<64 hex digits>" }
+//                    Both players commit to a secret for the roll being made
+//   { "type": "reveal", "secret": "<64 hex digits>" }
+//                    Once both have committed; the second reveal settles the roll
+//   { "type": "move", "from": 3 }        Path position of the piece to move, 0 = start
+//   { "type": "new_game" }               Once the game is over
+//
+// Server to client:
+//   { "type": "joined", "room": "club", "player": "Light" }
+//   { "type": "watching", "room": "club", "delay": 30 }
+//   { "type": "event", "event": { "type": "PieceMoved", "player": "Light", "from": 3, "to": 4 } }
+//                    Sent to everyone in the room for each GameEvent, before the state
+//   { "type": "state", "variant": "Finkel", "notation": "20 7/0 7/0 L -", "current_player": "Light",
+//     "dice_value": 0, "game_over": false, "winner": null, "light": "Ann", "dark": null,
+//     "message": "Light rolled 3", "time_control": null, "light_time_ms": null, "dark_time_ms": null,
+//     "clock_running": null, "fair_roll": null, "last_roll": { "round": 4, "dice": "FourBinary",
+//     "commitments": ["…", "…"], "secrets": ["…", "…"], "value": 3 } }
+//                    Sent to everyone in the room after each change. fair_roll is the roll
+//                    being made, and last_roll the full transcript of the one before, so
+//                    each player can check it with FairRoll::verify. The times are what was
+//                    left on each clock when the message was sent. While a roll waits only
+//                    on the opponent, their clock runs instead of the mover's
+//   { "type": "error", "message": "It's not your turn" }
+//
+// Rolls come from both players' secrets, so the server can't pick them; it passes turns
+// that can't move anything and checks every move. A player who holds up a roll for
+// ROLL_TIMEOUT, by not committing or not revealing, forfeits the game
+
+// How long a fair roll waits on each commitment or secret
+pub const ROLL_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub type ClientId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Join { room: String, name: Option<String>, variant: Option<Variant>, time_control: Option<TimeControl> },
+    Watch { room: String, delay: Option<u64> },
+    Roll,
+    Commit { commitment: Commitment },
+    Reveal { secret: Secret },
+    Move { from: usize },
+    NewGame,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Joined { room: String, player: Player },
+    Watching { room: String, delay: u64 },
+    Event { event: GameEvent },
+    State(Box<RoomState>),
+    Error { message: String },
+}
+
+// What every client in a room sees
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoomState {
+    pub variant: Variant,
+    pub notation: String,
+    pub current_player: Player,
+    pub dice_value: u8,
+    pub game_over: bool,
+    pub winner: Option<Player>,
+    pub light: Option<String>,  // Names of the seated players
+    pub dark: Option<String>,
+    pub message: String,        // What just happened
+    pub time_control: Option<TimeControl>,
+    pub light_time_ms: Option<f64>,
+    pub dark_time_ms: Option<f64>,
+    pub clock_running: Option<Player>,  // Not always the player to move, see charge_roll_clock
+    pub fair_roll: Option<FairRoll>,
+    pub last_roll: Option<FairRoll>,
+}
+
+// A message for one client, to be sent once delay has passed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outgoing {
+    pub to: ClientId,
+    pub message: ServerMessage,
+    pub delay: Duration,
+}
+
+impl Outgoing {
+    pub fn now(to: ClientId, message: ServerMessage) -> Outgoing {
+        Outgoing { to, message, delay: Duration::ZERO }
+    }
+}
+
+struct Seat {
+    client: ClientId,
+    name: String,
+}
+
+struct Spectator {
+    client: ClientId,
+    delay: Duration,
+}
+
+struct Room {
+    time: Box<dyn TimeSource>,
+    game: GameState,
+    seats: [Option<Seat>; 2],  // Light, then Dark
+    spectators: Vec<Spectator>,
+    message: String,
+    fair_roll: Option<FairRoll>,  // Waiting for commitments or secrets
+    roll_deadline: f64,           // When the roll in progress times out
+    last_roll: Option<FairRoll>,
+    rounds: u64,                  // Fair rolls started in this room
+    time_control: Option<TimeControl>,  // Applied once both players are seated
+}
+
+impl Room {
+    // Finkel and untimed until a player sits down
+    fn new(time: Box<dyn TimeSource>) -> Room {
+        Room {
+            time,
+            game: GameState::new(RuleSet::finkel()),
+            seats: [None, None],
+            spectators: Vec::new(),
+            message: String::new(),
+            fair_roll: None,
+            roll_deadline: 0.0,
+            last_roll: None,
+            rounds: 0,
+            time_control: None,
+        }
+    }
+
+    fn seat_of(&self, client: ClientId) -> Option<Player> {
+        [Player::Light, Player::Dark].into_iter().find(|&player| {
+            self.seats[seat_index(player)].as_ref().is_some_and(|seat| seat.client == client)
+        })
+    }
+
+    // Everyone following the room, with how late their messages arrive
+    fn audience(&self) -> impl Iterator<Item = (ClientId, Duration)> + '_ {
+        let players = self.seats.iter().flatten().map(|seat| (seat.client, Duration::ZERO));
+        players.chain(self.spectators.iter().map(|spectator| (spectator.client, spectator.delay)))
+    }
+
+    fn state(&self) -> RoomState {
+        let name = |player| self.seats[seat_index(player)].as_ref().map(|seat: &Seat| seat.name.clone());
+        RoomState {
+            variant: self.game.rules().variant,
+            notation: self.game.to_notation(),
+            current_player: self.game.current_player(),
+            dice_value: self.game.dice_value(),
+            game_over: self.game.game_over(),
+            winner: self.game.winner(),
+            light: name(Player::Light),
+            dark: name(Player::Dark),
+            message: self.message.clone(),
+            time_control: self.game.time_control().or(self.time_control),
+            light_time_ms: self.game.remaining_millis(Player::Light),
+            dark_time_ms: self.game.remaining_millis(Player::Dark),
+            clock_running: self.game.clock_running(),
+            fair_roll: self.fair_roll.clone(),
+            last_roll: self.last_roll.clone(),
+        }
+    }
+
+    // A new game with the given settings, for the first player to sit down or once a player
+    // leaves before the first roll
+    fn set_up(&mut self, variant: Variant, time_control: Option<TimeControl>) {
+        self.game = GameState::new(RuleSet::for_variant(variant));
+        self.time_control = time_control;
+        self.fair_roll = None;
+        self.last_roll = None;
+    }
+
+    // Plays for the seated player, or explains why they can't
+    fn act(&mut self, player: Player, message: &ClientMessage) -> Result<(), String> {
+        match *message {
+            ClientMessage::NewGame => {
+                if !self.game.game_over() {
+                    return Err("The game is still going".to_string());
+                }
+                self.game.reset();
+                self.fair_roll = None;
+                self.message = "New game".to_string();
+                return Ok(());
+            }
+            _ if self.check_time() || self.check_roll() => return Ok(()),
+            // Both players take part in every roll, whoever's turn it is
+            ClientMessage::Commit { commitment } => return self.commit(player, commitment),
+            ClientMessage::Reveal { secret } => return self.reveal(player, secret),
+            _ => {}
+        }
+        if self.game.game_over() {
+            return Err("The game is over".to_string());
+        }
+        if self.game.current_player() != player {
+            return Err("It's not your turn".to_string());
+        }
+
+        match *message {
+            ClientMessage::Roll => {
+                if self.game.dice_value() != 0 || self.fair_roll.is_some() {
+                    return Err("You've already rolled".to_string());
+                }
+                if self.seats.iter().any(Option::is_none) {
+                    return Err("Wait for an opponent before rolling".to_string());
+                }
+                self.rounds += 1;
+                self.fair_roll = Some(FairRoll::new(self.rounds, self.game.rules().dice));
+                self.extend_roll_deadline();
+                self.message = format!("{} is rolling", player.name());
+            }
+            ClientMessage::Move { from } => {
+                let game = &mut self.game;
+                if !game.can_move(from) {
+                    return Err(game.check_move(from).err().map_or("Illegal move".to_string(), |error| error.to_string()));
+                }
+                let outcome = game.make_move(from).map_err(|error| error.to_string())?;
+                self.message = match outcome.winner {
+                    Some(winner) => format!("{} wins!", winner.name()),
+                    None if outcome.extra_turn => format!("{} rolls again", player.name()),
+                    None if outcome.mv.captures => format!("{} captured a piece", player.name()),
+                    None => String::new(),
+                };
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Ends the game if the player to move is out of time; true if that just happened
+    fn check_time(&mut self) -> bool {
+        if !self.game.check_time() {
+            return false;
+        }
+        let loser = self.game.winner().expect("the game was won on time").opponent();
+        self.fair_roll = None;
+        self.message = format!("{} ran out of time. {} wins!", loser.name(), loser.opponent().name());
+        true
+    }
+
+    // Ends the game against whoever is holding up the roll in progress once it has waited
+    // too long; true if that just happened. A roll waiting on both players is called off
+    fn check_roll(&mut self) -> bool {
+        if self.fair_roll.is_none() || self.time.now_millis() < self.roll_deadline {
+            return false;
+        }
+        let roll = self.fair_roll.take().expect("a roll is in progress");
+        let stalling = roll_waiting_on(&roll);
+        self.message = match stalling[..] {
+            [player] => {
+                self.game.forfeit(player);
+                let step = if roll.all_committed() { "reveal" } else { "commit" };
+                format!("{} didn't {} in time and forfeits. {} wins!", player.name(), step, player.opponent().name())
+            }
+            _ => "Nobody took part in the roll, so it was called off".to_string(),
+        };
+        self.charge_roll_clock();
+        true
+    }
+
+    // Each step of a roll gets the full timeout
+    fn extend_roll_deadline(&mut self) {
+        self.roll_deadline = self.time.now_millis() + ROLL_TIMEOUT.as_secs_f64() * 1000.0;
+    }
+
+    // A roll waiting only on the opponent runs their clock rather than the mover's, so the
+    // wait is charged to whoever is causing it
+    fn charge_roll_clock(&mut self) {
+        let waiting = self.fair_roll.as_ref().map(roll_waiting_on).unwrap_or_default();
+        let charged = match waiting[..] {
+            [player] => player,
+            _ => self.game.current_player(),
+        };
+        self.game.charge_clock(charged);
+    }
+
+    fn commit(&mut self, player: Player, commitment: Commitment) -> Result<(), String> {
+        let roll = self.fair_roll.as_mut().ok_or("No roll is being made")?;
+        roll.commit(player, commitment).map_err(|error| error.to_string())?;
+        self.extend_roll_deadline();
+        self.charge_roll_clock();
+        Ok(())
+    }
+
+    fn reveal(&mut self, player: Player, secret: Secret) -> Result<(), String> {
+        let roll = self.fair_roll.as_mut().ok_or("No roll is being made")?;
+        let Some(value) = roll.reveal(player, secret).map_err(|error| error.to_string())? else {
+            self.extend_roll_deadline();
+            self.charge_roll_clock();
+            return Ok(());
+        };
+        self.last_roll = self.fair_roll.take();
+        self.charge_roll_clock();
+        if self.check_time() {
+            return Ok(());
+        }
+
+        // Roll through the game so the roll is kept in its history, then put the dice back
+        let game = &mut self.game;
+        let mover = game.current_player();
+        let dice = game.dice_source().clone_box();
+        game.set_dice_source(Box::new(ScriptedDice::new(vec![value])));
+        let rolled = game.roll_dice();
+        game.set_dice_source(dice);
+        rolled.map_err(|error| error.to_string())?;
+        self.message = if value == 0 {
+            format!("{} rolled 0. Turn passes.", mover.name())
+        } else if game.legal_moves().is_empty() {
+            game.pass_turn();
+            format!("{} rolled {} but nothing can move. Turn passes.", mover.name(), value)
+        } else {
+            format!("{} rolled {}", mover.name(), value)
+        };
+        Ok(())
+    }
+}
+
+// Players the roll still needs a commitment or, once both are in, a secret from
+fn roll_waiting_on(roll: &FairRoll) -> Vec<Player> {
+    let all_committed = roll.all_committed();
+    [Player::Light, Player::Dark]
+        .into_iter()
+        .filter(|&player| match all_committed {
+            false => roll.commitment(player).is_none(),
+            true => roll.secret(player).is_none(),
+        })
+        .collect()
+}
+
+fn seat_index(player: Player) -> usize {
+    match player {
+        Player::Light => 0,
+        Player::Dark => 1,
+    }
+}
+
+// Every hosted room, by name
+pub struct Lobby {
+    time: Box<dyn TimeSource>,          // For game clocks and roll deadlines
+    rooms: HashMap<String, Room>,
+    joined: HashMap<ClientId, String>,  // Room each client is in
+}
+
+impl Lobby {
+    pub fn new() -> Lobby {
+        Lobby::with_time(Box::new(SystemClock))
+    }
+
+    // Reading the time from the given source, e.g. a ManualClock to check timeouts
+    pub fn with_time(time: Box<dyn TimeSource>) -> Lobby {
+        Lobby { time, rooms: HashMap::new(), joined: HashMap::new() }
+    }
+
+    // Messages to send after a client's message
+    pub fn handle(&mut self, client: ClientId, message: ClientMessage) -> Vec<Outgoing> {
+        let result = match &message {
+            ClientMessage::Join { room, name, variant, time_control } => {
+                self.join(client, room, name.clone(), *variant, *time_control)
+            }
+            ClientMessage::Watch { room, delay } => self.watch(client, room, delay.unwrap_or(0)),
+            _ => self.act(client, &message),
+        };
+        result.unwrap_or_else(|error| vec![Outgoing::now(client, ServerMessage::Error { message: error })])
+    }
+
+    // Messages to send after a client disconnects. A player's seat is freed for someone
+    // else, after they forfeit any game still being played; before the first roll there is
+    // nothing to forfeit, so the game is set up afresh. Rooms nobody follows are closed
+    pub fn leave(&mut self, client: ClientId) -> Vec<Outgoing> {
+        let Some(name) = self.joined.remove(&client) else {
+            return Vec::new();
+        };
+        let Some(room) = self.rooms.get_mut(&name) else {
+            return Vec::new();
+        };
+        room.spectators.retain(|spectator| spectator.client != client);
+        let left = room.seat_of(client).map(|player| {
+            let seat = room.seats[seat_index(player)].take().expect("the player is seated");
+            (player, seat)
+        });
+        if room.audience().next().is_none() {
+            self.rooms.remove(&name);
+            return Vec::new();
+        }
+        let Some((player, seat)) = left else {
+            return Vec::new();
+        };
+        let called_off = room.fair_roll.take().is_some();
+        let started = !room.game.history().is_empty();
+        let forfeited = started && room.seats[seat_index(player.opponent())].is_some() && room.game.forfeit(player);
+        if !started {
+            // Also stops the clocks, which start again once both seats are taken
+            room.set_up(room.game.rules().variant, room.time_control);
+        }
+        room.message = if forfeited {
+            format!("{} left and forfeits. {} wins!", seat.name, player.opponent().name())
+        } else if called_off {
+            "A player left during a roll, so it was called off".to_string()
+        } else {
+            format!("{} left", seat.name)
+        };
+        broadcast(room)
+    }
+
+    // Messages to send as time passes: a game ends when the player to move runs out of time
+    // or a roll times out, so call this every so often
+    pub fn tick(&mut self) -> Vec<Outgoing> {
+        let mut outgoing = Vec::new();
+        for room in self.rooms.values_mut() {
+            if room.check_time() || room.check_roll() {
+                outgoing.extend(broadcast(room));
+            }
+        }
+        outgoing
+    }
+
+    fn join(
+        &mut self,
+        client: ClientId,
+        name: &str,
+        player_name: Option<String>,
+        variant: Option<Variant>,
+        time_control: Option<TimeControl>,
+    ) -> Result<Vec<Outgoing>, String> {
+        if self.joined.contains_key(&client) {
+            return Err("You've already joined a room".to_string());
+        }
+        let room = self.rooms.entry(name.to_string()).or_insert_with(|| Room::new(self.time.clone()));
+        if room.seats.iter().all(Option::is_none) {
+            // Also covers a room that spectators opened before anyone sat down
+            room.set_up(variant.unwrap_or(Variant::Finkel), time_control);
+        } else if variant.is_some_and(|variant| variant != room.game.rules().variant) {
+            return Err(format!("This room plays {}", room.game.rules().variant.name()));
+        } else if time_control.is_some_and(|control| Some(control) != room.time_control) {
+            return Err("This room has a different time control".to_string());
+        }
+        let player = [Player::Light, Player::Dark]
+            .into_iter()
+            .find(|&player| room.seats[seat_index(player)].is_none())
+            .ok_or("The room is full")?;
+        let player_name = player_name.unwrap_or_else(|| player.name().to_string());
+        room.message = format!("{} joined as {}", player_name, player.name());
+        room.seats[seat_index(player)] = Some(Seat { client, name: player_name });
+        self.joined.insert(client, name.to_string());
+        if let Some(control) = room.time_control.filter(|_| room.seats.iter().all(Option::is_some)) {
+            if room.game.time_control().is_none() {
+                room.game.set_time_control(control, room.time.clone());
+            }
+        }
+
+        let mut outgoing = vec![Outgoing::now(client, ServerMessage::Joined { room: name.to_string(), player })];
+        outgoing.extend(broadcast(room));
+        Ok(outgoing)
+    }
+
+    // Spectators start from the current state, so with a delay they see the game as it
+    // was that long ago and follow on from there
+    fn watch(&mut self, client: ClientId, name: &str, delay: u64) -> Result<Vec<Outgoing>, String> {
+        if self.joined.contains_key(&client) {
+            return Err("You've already joined a room".to_string());
+        }
+        let room = self.rooms.entry(name.to_string()).or_insert_with(|| Room::new(self.time.clone()));
+        let delay_time = Duration::from_secs(delay);
+        room.spectators.push(Spectator { client, delay: delay_time });
+        self.joined.insert(client, name.to_string());
+
+        Ok(vec![
+            Outgoing::now(client, ServerMessage::Watching { room: name.to_string(), delay }),
+            Outgoing { to: client, message: ServerMessage::State(Box::new(room.state())), delay: delay_time },
+        ])
+    }
+
+    fn act(&mut self, client: ClientId, message: &ClientMessage) -> Result<Vec<Outgoing>, String> {
+        let room = self.joined.get(&client)
+            .and_then(|name| self.rooms.get_mut(name))
+            .ok_or("Join a room first")?;
+        let player = room.seat_of(client).ok_or("Spectators can't play")?;
+        room.act(player, message)?;
+        Ok(broadcast(room))
+    }
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Lobby::new()
+    }
+}
+
+// The game's new events, then its state, for everyone following the room
+fn broadcast(room: &mut Room) -> Vec<Outgoing> {
+    let mut messages: Vec<ServerMessage> = room.game.drain_events().into_iter().map(|event| ServerMessage::Event { event }).collect();
+    messages.push(ServerMessage::State(Box::new(room.state())));
+    room.audience()
+        .flat_map(|(to, delay)| messages.iter().map(move |message| Outgoing { to, message: message.clone(), delay }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::dice::DiceKind;
+    use crate::fair_dice::roll_value;
+
+    fn join(variant: Option<Variant>) -> ClientMessage {
+        ClientMessage::Join { room: "club".to_string(), name: None, variant, time_control: None }
+    }
+
+    fn watch() -> ClientMessage {
+        ClientMessage::Watch { room: "club".to_string(), delay: None }
+    }
+
+    // The last state sent to the client
+    fn state_for(outgoing: &[Outgoing], client: ClientId) -> RoomState {
+        outgoing.iter().rev().find_map(|out| match &out.message {
+            ServerMessage::State(state) if out.to == client => Some(state.as_ref().clone()),
+            _ => None,
+        }).expect("a state was sent")
+    }
+
+    fn is_error(outgoing: &[Outgoing]) -> bool {
+        matches!(outgoing, [Outgoing { message: ServerMessage::Error { .. }, .. }])
+    }
+
+    #[test]
+    fn leaving_a_game_in_play_forfeits_it() {
+        let (mut lobby, _) = seated_lobby();
+        lobby.handle(3, watch());
+        make_fair_roll(&mut lobby);
+
+        let outgoing = lobby.leave(1);
+        let forfeited = ServerMessage::Event { event: GameEvent::Forfeited { player: Player::Light } };
+        assert!(outgoing.iter().any(|out| out.to == 3 && out.message == forfeited));
+        let state = state_for(&outgoing, 2);
+        assert_eq!((state.game_over, state.winner, state.light), (true, Some(Player::Dark), None));
+
+        // The next player takes the seat of a finished game, not the one in play
+        let outgoing = lobby.handle(4, join(None));
+        assert!(state_for(&outgoing, 4).game_over);
+    }
+
+    #[test]
+    fn leaving_before_the_game_starts_frees_the_seat() {
+        let (mut lobby, time) = seated_lobby();
+        lobby.rooms.get_mut("club").unwrap().game.set_time_control(TimeControl::sudden_death(1000), Box::new(time.clone()));
+        lobby.handle(1, ClientMessage::Roll);
+        let state = state_for(&lobby.leave(1), 2);
+        assert!(!state.game_over);
+        assert_eq!((state.light, state.fair_roll, state.clock_running), (None, None, None));
+        assert_eq!(state.message, "A player left during a roll, so it was called off");
+
+        time.advance(2000.0);
+        assert!(lobby.tick().is_empty());
+        lobby.handle(3, join(None));
+        assert_eq!(lobby.rooms["club"].game.winner(), None);
+    }
+
+    #[test]
+    fn rooms_close_when_their_last_follower_leaves() {
+        let mut lobby = Lobby::new();
+        lobby.handle(1, watch());
+        lobby.handle(2, watch());
+        lobby.leave(1);
+        assert!(lobby.rooms.contains_key("club"));
+        lobby.leave(2);
+        assert!(lobby.rooms.is_empty());
+    }
+
+    // A lobby on a manual clock with both seats of "club" taken, Light by client 1
+    fn seated_lobby() -> (Lobby, ManualClock) {
+        let time = ManualClock::new();
+        let mut lobby = Lobby::with_time(Box::new(time.clone()));
+        lobby.handle(1, join(None));
+        lobby.handle(2, join(None));
+        (lobby, time)
+    }
+
+    fn secret(byte: u8) -> Secret {
+        Secret::from_bytes([byte; 32])
+    }
+
+    // Light rolls with secret 1 and Dark with secret 2, returning the last messages
+    fn make_fair_roll(lobby: &mut Lobby) -> Vec<Outgoing> {
+        let round = lobby.rooms["club"].rounds + 1;
+        lobby.handle(1, ClientMessage::Roll);
+        for (client, byte) in [(1, 1), (2, 2)] {
+            lobby.handle(client, ClientMessage::Commit { commitment: secret(byte).commitment(round) });
+        }
+        lobby.handle(2, ClientMessage::Reveal { secret: secret(2) });
+        lobby.handle(1, ClientMessage::Reveal { secret: secret(1) })
+    }
+
+    #[test]
+    fn a_fair_roll_leaves_the_game_dice_alone() {
+        let (mut lobby, _) = seated_lobby();
+        let outgoing = make_fair_roll(&mut lobby);
+
+        let state = state_for(&outgoing, 2);
+        assert_eq!(state.last_roll.and_then(|roll| roll.value), Some(roll_value(DiceKind::FourBinary, 1, &secret(1), &secret(2))));
+        assert!(lobby.rooms["club"].game.dice_source().seed().is_some());
+    }
+
+    #[test]
+    fn holding_up_a_roll_forfeits_the_game() {
+        let (mut lobby, time) = seated_lobby();
+        lobby.handle(1, ClientMessage::Roll);
+        lobby.handle(1, ClientMessage::Commit { commitment: secret(1).commitment(1) });
+
+        time.advance(ROLL_TIMEOUT.as_secs_f64() * 1000.0 - 1.0);
+        assert!(lobby.tick().is_empty());
+        time.advance(1.0);
+        let state = state_for(&lobby.tick(), 1);
+        assert_eq!((state.game_over, state.winner), (true, Some(Player::Light)));
+        assert_eq!(state.fair_roll, None);
+    }
+
+    #[test]
+    fn holding_up_a_roll_runs_your_own_clock() {
+        let time = ManualClock::new();
+        let mut lobby = Lobby::with_time(Box::new(time.clone()));
+        let timed = ClientMessage::Join {
+            room: "club".to_string(),
+            name: None,
+            variant: None,
+            time_control: Some(TimeControl::sudden_death(10_000)),
+        };
+        lobby.handle(1, timed);
+        lobby.handle(2, join(None));
+        lobby.handle(1, ClientMessage::Roll);
+        let outgoing = lobby.handle(1, ClientMessage::Commit { commitment: secret(1).commitment(1) });
+        assert_eq!(state_for(&outgoing, 1).clock_running, Some(Player::Dark));
+
+        time.advance(10_000.0);
+        let state = state_for(&lobby.tick(), 1);
+        assert_eq!((state.game_over, state.winner), (true, Some(Player::Light)));
+        assert_eq!(state.light_time_ms, Some(10_000.0));
+        assert_eq!(state.message, "Dark ran out of time. Light wins!");
+    }
+
+    #[test]
+    fn a_roll_nobody_takes_part_in_is_called_off() {
+        let (mut lobby, time) = seated_lobby();
+        lobby.handle(1, ClientMessage::Roll);
+        time.advance(ROLL_TIMEOUT.as_secs_f64() * 1000.0);
+        let state = state_for(&lobby.tick(), 2);
+        assert!(!state.game_over);
+        assert_eq!(state.fair_roll, None);
+    }
+
+    #[test]
+    fn the_first_player_seated_sets_up_the_room() {
+        let mut lobby = Lobby::new();
+        lobby.handle(1, watch());
+        let outgoing = lobby.handle(2, join(Some(Variant::Masters)));
+        assert_eq!(state_for(&outgoing, 1).variant, Variant::Masters);
+
+        assert!(is_error(&lobby.handle(3, join(Some(Variant::Finkel)))));
+        let timed = ClientMessage::Join {
+            room: "club".to_string(),
+            name: None,
+            variant: None,
+            time_control: Some(TimeControl::sudden_death(60_000)),
+        };
+        assert!(is_error(&lobby.handle(3, timed)));
+        let outgoing = lobby.handle(3, join(None));
+        assert_eq!(state_for(&outgoing, 3).variant, Variant::Masters);
+    }
+}
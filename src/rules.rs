@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::board::{BoardError, BoardLayout, Route};
+use crate::dice::DiceKind;
+use crate::Player;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub enum Variant {
+    Finkel,
+    Masters,
+    Blitz,
+    Aseb,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 4] = [Variant::Finkel, Variant::Masters, Variant::Blitz, Variant::Aseb];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Finkel => "Finkel",
+            Variant::Masters => "Masters",
+            Variant::Blitz => "Blitz",
+            Variant::Aseb => "Aseb",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL.into_iter().find(|variant| variant.name() == name)
+    }
+}
+
+// Standard board (indices): left block 0-11 (3 rows of 4), bridge 12-13, right block 14-19
+// (3 rows of 2). Light owns the top row (0-3, 14-15), Dark owns the bottom row (8-11, 18-19)
+// and the middle row (4-7, 12-13, 16-17) is shared
+
+// Finkel: own row leftwards, middle row rightwards, back along own row at the far end
+const FINKEL_LIGHT_PATH: [usize; 14] = [3, 2, 1, 0, 4, 5, 6, 7, 12, 13, 16, 17, 15, 14];
+const FINKEL_DARK_PATH: [usize; 14] = [11, 10, 9, 8, 4, 5, 6, 7, 12, 13, 16, 17, 19, 18];
+
+// Masters: the middle row stops one square short, then the path loops around the far end
+// through the opponent's row, the last middle square and back along own row
+const MASTERS_LIGHT_PATH: [usize; 16] = [3, 2, 1, 0, 4, 5, 6, 7, 12, 13, 16, 18, 19, 17, 15, 14];
+const MASTERS_DARK_PATH: [usize; 16] = [11, 10, 9, 8, 4, 5, 6, 7, 12, 13, 16, 14, 15, 17, 19, 18];
+
+// Aseb: own row leftwards, then all the way down the middle row and its straight lane
+const ASEB_LIGHT_PATH: [usize; 16] = [3, 2, 1, 0, 4, 5, 6, 7, 12, 13, 14, 15, 16, 17, 18, 19];
+const ASEB_DARK_PATH: [usize; 16] = [11, 10, 9, 8, 4, 5, 6, 7, 12, 13, 14, 15, 16, 17, 18, 19];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleSet {
+    pub variant: Variant,
+    pub pieces: u8,                        // Pieces per player
+    layout: BoardLayout,
+    light_route: Route,
+    dark_route: Route,
+    pub safe_rosettes: bool,               // Pieces on rosettes can't be captured
+    pub rosettes_grant_extra_roll: bool,
+    pub captures_grant_extra_roll: bool,
+    pub exact_bear_off: bool,              // Leaving the board needs the exact roll
+    pub dice: DiceKind,
+}
+
+impl RuleSet {
+    pub fn finkel() -> RuleSet {
+        RuleSet::builtin(Variant::Finkel, BoardLayout::standard(), &FINKEL_LIGHT_PATH, &FINKEL_DARK_PATH)
+    }
+
+    // Masters' rules change the route and the dice but not the board: the loop around the
+    // far end runs through both far-corner rosettes, so the standard rosettes are the ones
+    // its players land on
+    pub fn masters() -> RuleSet {
+        RuleSet {
+            dice: DiceKind::ThreeBinaryZeroMax,
+            ..RuleSet::builtin(Variant::Masters, BoardLayout::standard(), &MASTERS_LIGHT_PATH, &MASTERS_DARK_PATH)
+        }
+    }
+
+    pub fn blitz() -> RuleSet {
+        // Fewer pieces, no safe squares, and every capture earns another roll
+        RuleSet {
+            variant: Variant::Blitz,
+            pieces: 5,
+            safe_rosettes: false,
+            captures_grant_extra_roll: true,
+            ..RuleSet::masters()
+        }
+    }
+
+    pub fn aseb() -> RuleSet {
+        RuleSet {
+            pieces: 5,
+            ..RuleSet::builtin(Variant::Aseb, BoardLayout::aseb(), &ASEB_LIGHT_PATH, &ASEB_DARK_PATH)
+        }
+    }
+
+    pub fn for_variant(variant: Variant) -> RuleSet {
+        match variant {
+            Variant::Finkel => RuleSet::finkel(),
+            Variant::Masters => RuleSet::masters(),
+            Variant::Blitz => RuleSet::blitz(),
+            Variant::Aseb => RuleSet::aseb(),
+        }
+    }
+
+    // Finkel rules on a custom board, with each player's route given as board indices
+    pub fn with_board(
+        variant: Variant,
+        layout: BoardLayout,
+        light_path: Vec<usize>,
+        dark_path: Vec<usize>,
+    ) -> Result<RuleSet, BoardError> {
+        let light_route = Route::new(&layout, light_path)?;
+        let dark_route = Route::new(&layout, dark_path)?;
+        Ok(RuleSet {
+            variant,
+            layout,
+            light_route,
+            dark_route,
+            ..RuleSet::finkel()
+        })
+    }
+
+    fn builtin(variant: Variant, layout: BoardLayout, light_path: &[usize], dark_path: &[usize]) -> RuleSet {
+        let light_route = Route::new(&layout, light_path.to_vec()).expect("built-in routes are valid");
+        let dark_route = Route::new(&layout, dark_path.to_vec()).expect("built-in routes are valid");
+        RuleSet {
+            variant,
+            pieces: 7,
+            layout,
+            light_route,
+            dark_route,
+            safe_rosettes: true,
+            rosettes_grant_extra_roll: true,
+            captures_grant_extra_roll: false,
+            exact_bear_off: true,
+            dice: DiceKind::FourBinary,
+        }
+    }
+
+    pub fn layout(&self) -> &BoardLayout {
+        &self.layout
+    }
+
+    pub fn route(&self, player: Player) -> &Route {
+        match player {
+            Player::Light => &self.light_route,
+            Player::Dark => &self.dark_route,
+        }
+    }
+
+    // Path position reached by bearing a piece off the board
+    pub fn off_position(&self, player: Player) -> usize {
+        self.route(player).off_position()
+    }
+
+    // Most pieces each player can have: counts are single bytes in notation and records,
+    // and position keys have to fit in a u64
+    pub fn max_pieces(&self) -> u8 {
+        (1..=u8::MAX)
+            .rev()
+            .find(|&pieces| RuleSet { pieces, ..self.clone() }.position_count().is_some())
+            .unwrap_or(0)
+    }
+
+    // Size of the position key space: dice value, side to move, pieces off for each player
+    // and each square empty, Light or Dark. None if it doesn't fit in a u64
+    pub fn position_count(&self) -> Option<u64> {
+        let counts = u64::from(self.pieces) + 1;
+        let mut total = (u64::from(self.dice.max_value()) + 1) * 2 * counts * counts;
+        for _ in 0..self.layout.len() {
+            total = total.checked_mul(3)?;
+        }
+        Some(total)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::finkel()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Path positions of the rosettes on each player's route
+    fn rosettes_on_route(rules: &RuleSet, player: Player) -> Vec<usize> {
+        let route = rules.route(player);
+        (1..route.off_position()).filter(|&path_pos| {
+            route.board_index(path_pos).is_some_and(|index| rules.layout().is_rosette(index))
+        }).collect()
+    }
+
+    #[test]
+    fn each_variant_has_its_routes_and_rosettes() {
+        let cases = [
+            (Variant::Finkel, 14, [0, 7, 8, 14, 18], vec![4, 8, 14], vec![4, 8, 14]),
+            (Variant::Masters, 16, [0, 7, 8, 14, 18], vec![4, 8, 12, 16], vec![4, 8, 12, 16]),
+            (Variant::Blitz, 16, [0, 7, 8, 14, 18], vec![4, 8, 12, 16], vec![4, 8, 12, 16]),
+            (Variant::Aseb, 16, [0, 7, 8, 15, 19], vec![4, 8, 12, 16], vec![4, 8, 12, 16]),
+        ];
+        for (variant, length, rosettes, light, dark) in cases {
+            let rules = RuleSet::for_variant(variant);
+            assert_eq!(rules.variant, variant);
+            let board: Vec<usize> = (0..rules.layout().len()).filter(|&index| rules.layout().is_rosette(index)).collect();
+            assert_eq!(board, rosettes, "{:?}", variant);
+            assert_eq!(rosettes_on_route(&rules, Player::Light), light, "{:?}", variant);
+            assert_eq!(rosettes_on_route(&rules, Player::Dark), dark, "{:?}", variant);
+
+            // Each player enters along their own row, then shares the middle row from path 5
+            let (light_route, dark_route) = (rules.route(Player::Light), rules.route(Player::Dark));
+            assert_eq!((light_route.len(), dark_route.len()), (length, length));
+            assert_eq!(&light_route.squares()[..4], &[3, 2, 1, 0]);
+            assert_eq!(&dark_route.squares()[..4], &[11, 10, 9, 8]);
+            assert_eq!(light_route.squares()[4..10], dark_route.squares()[4..10]);
+        }
+    }
+
+    #[test]
+    fn variants_differ_in_their_rules() {
+        let finkel = RuleSet::finkel();
+        assert_eq!((finkel.pieces, finkel.dice), (7, DiceKind::FourBinary));
+        assert!(finkel.safe_rosettes && finkel.exact_bear_off && !finkel.captures_grant_extra_roll);
+        assert_eq!(RuleSet::masters().dice, DiceKind::ThreeBinaryZeroMax);
+        let blitz = RuleSet::blitz();
+        assert_eq!(blitz.pieces, 5);
+        assert!(!blitz.safe_rosettes && blitz.captures_grant_extra_roll);
+        assert_eq!(RuleSet::aseb().pieces, 5);
+    }
+}
@@ -0,0 +1,554 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::ai::{agent_from_spec, Agent, Budget, Expectiminimax, Mcts, WinEstimator};
+use crate::dice::ScriptedDice;
+use crate::game::GameState;
+use crate::history::Action;
+use crate::moves::Move;
+use crate::record::{GameRecord, RecordedAction};
+use crate::rules::{RuleSet, Variant};
+
+// Line-based engine protocol in the style of UCI. The controller sends commands on the
+// engine's stdin and reads replies from its stdout, one per line:
+//
+//   ugi                          -> id name ..., id author ..., option ..., ugiok
+//   isready                      -> readyok
+//   newgame [variant]            Opening position, optionally of another variant
+//   setoption name Player value <player>
+//                                Engine that go uses: expectiminimax:3 by default, or any
+//                                tournament player (random, greedy, mcts:<iterations>, ...)
+//   position startpos [actions <token>...]
+//   position notation <notation> [actions <token>...]
+//                                Sets the position, then plays game record tokens (L3 0-3 pass)
+//   roll <n>                     The player to move rolled n
+//   move <from>-<to>             Plays a move, written as in game records
+//   pass                         Passes after a roll that can't move anything
+//   go [depth <n> | iterations <n> | movetime <ms>]
+//                                -> info ..., bestmove <from>-<to> or bestmove pass
+//   eval                         -> info winprob <chance the player to move wins>
+//   show                         -> position notation <notation>
+//   quit
+//
+// Commands that can't be carried out reply with error <message> and change nothing. Engines
+// play a variant's built-in rules, since newgame doesn't carry pieces or dice
+
+const DEFAULT_PLAYER: &str = "expectiminimax:3";
+
+// How long the controller waits for each reply line before giving up on the engine
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// The engine side: keeps a game and answers commands
+pub struct UgiEngine {
+    game: GameState,
+    player: String,
+    estimator: WinEstimator,  // For eval, kept so repeated positions aren't searched again
+}
+
+impl UgiEngine {
+    pub fn new() -> UgiEngine {
+        UgiEngine { game: GameState::default(), player: DEFAULT_PLAYER.to_string(), estimator: WinEstimator::new() }
+    }
+
+    pub fn game(&self) -> &GameState {
+        &self.game
+    }
+
+    // Answers commands until quit or the end of the input
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let Some(replies) = self.handle(&line?) else {
+                break;
+            };
+            for reply in replies {
+                writeln!(output, "{}", reply)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    // Reply lines to one command, or None for quit. Blank lines and unknown commands
+    // get an empty reply or an error
+    pub fn handle(&mut self, line: &str) -> Option<Vec<String>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Some(Vec::new());
+        };
+        let result = match command {
+            "ugi" => Ok(vec![
+                format!("id name Game of Ur {}", env!("CARGO_PKG_VERSION")),
+                "id author Game of Ur contributors".to_string(),
+                format!("option name Player type string default {}", DEFAULT_PLAYER),
+                "ugiok".to_string(),
+            ]),
+            "isready" => Ok(vec!["readyok".to_string()]),
+            "newgame" => self.new_game(args),
+            "setoption" => self.set_option(args),
+            "position" => self.position(args),
+            "roll" => self.roll(args),
+            "move" => self.play(args),
+            "pass" => self.pass(),
+            "go" => self.go(args),
+            "eval" => Ok(vec![format!("info winprob {:.4}", self.estimator.win_probability(&self.game))]),
+            "show" => Ok(vec![format!("position notation {}", self.game.to_notation())]),
+            "quit" => return None,
+            _ => Err(format!("unknown command '{}'", command)),
+        };
+        Some(result.unwrap_or_else(|message| vec![format!("error {}", message)]))
+    }
+
+    fn new_game(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        let rules = match args.first() {
+            Some(name) => RuleSet::for_variant(Variant::from_name(name).ok_or(format!("unknown variant '{}'", name))?),
+            None => self.game.rules().clone(),
+        };
+        self.game = GameState::new(rules);
+        Ok(Vec::new())
+    }
+
+    fn set_option(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        match args {
+            ["name", "Player", "value", spec] => {
+                agent_from_spec(spec, 0).ok_or(format!("unknown player '{}'", spec))?;
+                self.player = spec.to_string();
+                Ok(Vec::new())
+            }
+            ["name", name, ..] => Err(format!("unknown option '{}'", name)),
+            _ => Err("expected setoption name <name> value <value>".to_string()),
+        }
+    }
+
+    fn position(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        let split = args.iter().position(|&word| word == "actions").unwrap_or(args.len());
+        let (start, actions) = (&args[..split], args.get(split + 1..).unwrap_or(&[]));
+        let position = match start {
+            ["startpos"] => None,
+            ["notation", notation @ ..] => Some(notation.join(" ")),
+            _ => return Err("expected position startpos or position notation <notation>".to_string()),
+        };
+
+        let actions = actions.iter().map(|token| token.parse()).collect::<Result<Vec<RecordedAction>, _>>();
+        let rules = self.game.rules().clone();
+        let record = GameRecord {
+            variant: rules.variant,
+            light: None,
+            dark: None,
+            date: None,
+            seed: None,
+            position,
+            result: None,
+            termination: None,
+            tags: Vec::new(),
+            actions: actions.map_err(|error| error.to_string())?,
+            comments: Vec::new(),
+        };
+        self.game = record.replay_with(rules).map_err(|error| error.to_string())?;
+        Ok(Vec::new())
+    }
+
+    fn roll(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        let value: u8 = match args {
+            [value] => value.parse().map_err(|_| format!("'{}' is not a roll", value))?,
+            _ => return Err("expected roll <n>".to_string()),
+        };
+        if self.game.game_over() {
+            return Err("the game is over".to_string());
+        }
+        if self.game.dice_value() != 0 {
+            return Err("the dice have already been rolled".to_string());
+        }
+        if !self.game.rules().dice.can_roll(value) {
+            return Err(format!("the dice can't roll {}", value));
+        }
+        // Roll through the game so the roll is kept in its history
+        let dice = self.game.dice_source().clone_box();
+        self.game.set_dice_source(Box::new(ScriptedDice::new(vec![value])));
+        let rolled = self.game.roll_dice();
+        self.game.set_dice_source(dice);
+        rolled.map_err(|error| error.to_string())?;
+        Ok(Vec::new())
+    }
+
+    fn play(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        let (from, to) = match args.first().map(|token| token.parse()) {
+            Some(Ok(RecordedAction::Move { from, to, .. })) => (from, to),
+            _ => return Err("expected move <from>-<to>".to_string()),
+        };
+        let mv = self.game.check_move(from).map_err(|error| error.to_string())?;
+        if mv.to != to {
+            return Err(format!("the piece on {} moves to {}", from, mv.to));
+        }
+        self.game.make_move(from).map_err(|error| error.to_string())?;
+        Ok(Vec::new())
+    }
+
+    fn pass(&mut self) -> Result<Vec<String>, String> {
+        if self.game.game_over() || self.game.dice_value() == 0 {
+            return Err("roll before passing".to_string());
+        }
+        self.game.pass_turn();
+        Ok(Vec::new())
+    }
+
+    fn go(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        if self.game.game_over() {
+            return Err("the game is over".to_string());
+        }
+        if self.game.dice_value() == 0 {
+            return Err("roll before searching".to_string());
+        }
+        if self.game.legal_moves().is_empty() {
+            return Ok(vec!["bestmove pass".to_string()]);
+        }
+
+        let number = |value: &str| value.parse::<u32>().map_err(|_| format!("'{}' is not a number", value));
+        let (info, mv) = match args {
+            ["depth", depth] => {
+                let result = Expectiminimax::new(number(depth)?).search(&self.game).ok_or("no move found")?;
+                (Some(format!("info depth {} value {:.4} nodes {}", depth, result.value, result.nodes)), result.mv)
+            }
+            ["iterations", iterations] => mcts(&self.game, Budget::Iterations(number(iterations)?))?,
+            ["movetime", millis] => mcts(&self.game, Budget::Millis(number(millis)? as f64))?,
+            [] => {
+                let mut agent = agent_from_spec(&self.player, self.game.zobrist_hash()).ok_or("no player set")?;
+                (None, agent.choose_move(&self.game).ok_or("no move found")?)
+            }
+            _ => return Err("expected go, go depth <n>, go iterations <n> or go movetime <ms>".to_string()),
+        };
+        Ok(info.into_iter().chain([format!("bestmove {}", move_token(&mv))]).collect())
+    }
+}
+
+impl Default for UgiEngine {
+    fn default() -> Self {
+        UgiEngine::new()
+    }
+}
+
+fn mcts(game: &GameState, budget: Budget) -> Result<(Option<String>, Move), String> {
+    let result = Mcts { seed: game.zobrist_hash(), ..Mcts::new(budget) }.search(game).ok_or("no move found")?;
+    let best = result.moves[0];
+    Ok((Some(format!("info iterations {} winrate {:.4}", result.iterations, best.win_rate)), result.mv))
+}
+
+// A move as written in game records, such as 0-3 or 6-8x*
+fn move_token(mv: &Move) -> String {
+    RecordedAction::from(Action::Move { mv: *mv }).to_string()
+}
+
+#[derive(Debug)]
+pub enum UgiError {
+    Io(io::Error),
+    EmptyCommand,
+    Closed,          // The engine exited or closed its output
+    Handshake,       // The engine didn't answer ugi with ugiok
+    Timeout,         // No reply line within the engine's timeout
+    Engine(String),  // The engine replied error <message>
+    CustomRules,     // newgame only names a variant, so engines play the built-in rules
+}
+
+impl fmt::Display for UgiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UgiError::Io(error) => write!(f, "{}", error),
+            UgiError::EmptyCommand => write!(f, "no engine command given"),
+            UgiError::Closed => write!(f, "the engine closed its output"),
+            UgiError::Handshake => write!(f, "the engine didn't answer ugi with ugiok"),
+            UgiError::Timeout => write!(f, "the engine didn't reply in time"),
+            UgiError::Engine(message) => write!(f, "the engine replied: {}", message),
+            UgiError::CustomRules => write!(f, "engines can only play the built-in rules of a variant"),
+        }
+    }
+}
+
+impl std::error::Error for UgiError {}
+
+impl From<io::Error> for UgiError {
+    fn from(error: io::Error) -> Self {
+        UgiError::Io(error)
+    }
+}
+
+// The controller side: an engine in another process, played as an Agent
+pub struct ExternalEngine {
+    child: Child,
+    input: ChildStdin,
+    replies: Receiver<String>,  // Lines of the engine's stdout, read on their own thread
+    name: String,
+    variant: Option<Variant>,   // Variant the engine was last told about
+    timeout: Duration,
+}
+
+impl ExternalEngine {
+    // Starts the engine from a command line such as "python3 bot.py" and waits for its ugiok
+    pub fn spawn(command: &str) -> Result<ExternalEngine, UgiError> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or(UgiError::EmptyCommand)?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().expect("stdin is piped");
+        let output = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        // Reading on another thread lets read_line give up on an engine that stays silent
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in output.lines() {
+                if line.ok().and_then(|line| sender.send(line).ok()).is_none() {
+                    break;
+                }
+            }
+        });
+        let mut engine = ExternalEngine {
+            child,
+            input,
+            replies,
+            name: command.to_string(),
+            variant: None,
+            timeout: REPLY_TIMEOUT,
+        };
+
+        // An engine that exits straight away may have closed its input already
+        engine.send("ugi").map_err(|_| UgiError::Handshake)?;
+        loop {
+            let line = engine.read_line().map_err(|_| UgiError::Handshake)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.to_string();
+            } else if line == "ugiok" {
+                return Ok(engine);
+            }
+        }
+    }
+
+    // Longest wait for each reply line, 30 seconds unless set
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    // Sends one command
+    pub fn send(&mut self, command: &str) -> Result<(), UgiError> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()?;
+        Ok(())
+    }
+
+    // Reads the engine's next reply line, waiting at most the timeout
+    pub fn read_line(&mut self) -> Result<String, UgiError> {
+        match self.replies.recv_timeout(self.timeout) {
+            Ok(line) => Ok(line.trim().to_string()),
+            Err(RecvTimeoutError::Timeout) => Err(UgiError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(UgiError::Closed),
+        }
+    }
+
+    // Tells the engine a new game of the variant is starting, for engines that keep
+    // anything from one game to the next
+    pub fn new_game(&mut self, variant: Variant) -> Result<(), UgiError> {
+        self.send(&format!("newgame {}", variant.name()))?;
+        self.variant = Some(variant);
+        Ok(())
+    }
+
+    // Asks for a move in the given position, returning the bestmove token. Fails at the
+    // first error reply, so an engine that rejects the position isn't waited on
+    pub fn best_move_token(&mut self, game: &GameState) -> Result<String, UgiError> {
+        let variant = game.rules().variant;
+        if *game.rules() != RuleSet::for_variant(variant) {
+            return Err(UgiError::CustomRules);
+        }
+        // Skips whatever is left over from an earlier request that failed
+        self.send("isready")?;
+        while self.read_line()? != "readyok" {}
+
+        if self.variant != Some(variant) {
+            self.new_game(variant)?;
+        }
+        self.send(&format!("position notation {}", game.to_notation()))?;
+        self.send("go")?;
+        let reply = self.read_best_move();
+        if reply.is_err() {
+            // The engine may not have taken the variant, so tell it again next time
+            self.variant = None;
+        }
+        reply
+    }
+
+    fn read_best_move(&mut self) -> Result<String, UgiError> {
+        loop {
+            let line = self.read_line()?;
+            if let Some(token) = line.strip_prefix("bestmove ") {
+                return Ok(token.trim().to_string());
+            }
+            if let Some(message) = line.strip_prefix("error") {
+                if message.is_empty() || message.starts_with(' ') {
+                    return Err(UgiError::Engine(message.trim().to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl Agent for ExternalEngine {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // Anything but a legal bestmove, including an error reply or an engine that doesn't
+    // answer in time, counts as no move
+    fn choose_move(&mut self, game: &GameState) -> Option<Move> {
+        let token = self.best_move_token(game).ok()?;
+        match token.parse().ok()? {
+            RecordedAction::Move { from, to, .. } => game.check_move(from).ok().filter(|mv| mv.to == to),
+            _ => None,
+        }
+    }
+}
+
+// Asks the engine to quit, and stops it anyway in case it doesn't
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(engine: &mut UgiEngine, line: &str) -> Vec<String> {
+        engine.handle(line).expect("only quit ends the session")
+    }
+
+    fn is_error(replies: &[String]) -> bool {
+        matches!(replies, [line] if line.starts_with("error "))
+    }
+
+    #[test]
+    fn positions_are_set_from_notation_or_actions() {
+        let mut engine = UgiEngine::new();
+        assert_eq!(reply(&mut engine, "position startpos actions L3 0-3 D0"), Vec::<String>::new());
+        assert_eq!(reply(&mut engine, "show"), ["position notation 1L18 6/0 7/0 L -"]);
+
+        reply(&mut engine, "position notation 5LD13 6/0 6/0 L -");
+        assert_eq!(engine.game().to_notation(), "5LD13 6/0 6/0 L -");
+
+        // A move that lands elsewhere, or a bad token, leaves the position alone
+        assert!(is_error(&reply(&mut engine, "position startpos actions L3 0-2")));
+        assert!(is_error(&reply(&mut engine, "position startpos actions L3 sideways")));
+        assert!(is_error(&reply(&mut engine, "position notation 21 7/0 7/0 L -")));
+        assert_eq!(engine.game().to_notation(), "5LD13 6/0 6/0 L -");
+    }
+
+    #[test]
+    fn rolls_are_checked() {
+        let mut engine = UgiEngine::new();
+        assert!(is_error(&reply(&mut engine, "roll 5")));
+        assert!(is_error(&reply(&mut engine, "roll two")));
+        assert!(reply(&mut engine, "roll 2").is_empty());
+        assert!(is_error(&reply(&mut engine, "roll 2")));
+        assert_eq!(engine.game().dice_value(), 2);
+        assert!(matches!(engine.game().history()[..], [Action::Roll { value: 2, .. }]));
+    }
+
+    #[test]
+    fn go_answers_with_a_legal_move() {
+        let mut engine = UgiEngine::new();
+        assert!(is_error(&reply(&mut engine, "go")));
+        reply(&mut engine, "roll 3");
+        for search in ["go", "go depth 2", "go iterations 50"] {
+            let replies = reply(&mut engine, search);
+            assert_eq!(replies.last().unwrap(), "bestmove 0-3", "{}", search);
+        }
+        assert!(is_error(&reply(&mut engine, "go depth deep")));
+
+        // Light's only piece is stuck behind Dark's on the central rosette
+        reply(&mut engine, "position notation 6LD12 0/6 6/0 L -");
+        reply(&mut engine, "roll 1");
+        assert_eq!(reply(&mut engine, "go"), ["bestmove pass"]);
+        assert!(reply(&mut engine, "pass").is_empty());
+        assert_eq!(engine.game().current_player(), crate::game::Player::Dark);
+    }
+
+    #[test]
+    fn sessions_run_until_quit() {
+        let input = "ugi\nisready\nfly\n\nquit\nisready\n";
+        let mut output = Vec::new();
+        UgiEngine::new().run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[3..], ["ugiok", "readyok", "error unknown command 'fly'"]);
+    }
+
+    // An engine written as a shell script, answering the handshake and then each go with
+    // the given line, or not at all
+    #[cfg(unix)]
+    fn script_engine(name: &str, go_reply: &str) -> Result<ExternalEngine, UgiError> {
+        let path = std::env::temp_dir().join(format!("ugi-{}-{}.sh", name, std::process::id()));
+        let answer = if go_reply.is_empty() { ":".to_string() } else { format!("echo '{}'", go_reply) };
+        let script = format!(
+            "while read command rest; do case $command in\n\
+             ugi) echo 'id name {}'; echo ugiok;;\n\
+             isready) echo readyok;;\n\
+             go) {};;\n\
+             quit) exit;;\n\
+             esac; done\n",
+            name, answer,
+        );
+        std::fs::write(&path, script)?;
+        // The shell has the script open once the handshake is done
+        let engine = ExternalEngine::spawn(&format!("sh {}", path.display()));
+        let _ = std::fs::remove_file(&path);
+        let mut engine = engine?;
+        engine.set_timeout(Duration::from_millis(500));
+        Ok(engine)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_engines_play_their_bestmove() {
+        let mut engine = script_engine("enterer", "bestmove 0-2").unwrap();
+        assert_eq!(engine.name(), "enterer");
+        let game = GameState::from_notation(RuleSet::finkel(), "20 7/0 7/0 L 2").unwrap();
+        assert_eq!(engine.choose_move(&game).map(|mv| (mv.from, mv.to)), Some((0, 2)));
+        // The same reply is no move on a roll of 3
+        let game = GameState::from_notation(RuleSet::finkel(), "20 7/0 7/0 L 3").unwrap();
+        assert_eq!(engine.choose_move(&game), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_engine_errors_and_silence_are_no_move() {
+        let game = GameState::from_notation(RuleSet::finkel(), "20 7/0 7/0 L 2").unwrap();
+
+        let mut refuser = script_engine("refuser", "error no thanks").unwrap();
+        assert!(matches!(refuser.best_move_token(&game), Err(UgiError::Engine(message)) if message == "no thanks"));
+        assert_eq!(refuser.choose_move(&game), None);
+
+        let mut silent = script_engine("silent", "").unwrap();
+        assert!(matches!(silent.best_move_token(&game), Err(UgiError::Timeout)));
+        // Asking again sends a fresh position rather than waiting on the old request
+        assert_eq!(silent.choose_move(&game), None);
+
+        let mut rules = RuleSet::finkel();
+        rules.pieces = 3;
+        let custom = GameState::new(rules);
+        assert!(matches!(silent.best_move_token(&custom), Err(UgiError::CustomRules)));
+    }
+
+    #[test]
+    fn engines_that_never_start_are_reported() {
+        assert!(matches!(ExternalEngine::spawn("   "), Err(UgiError::EmptyCommand)));
+        assert!(matches!(ExternalEngine::spawn("no-such-engine-anywhere"), Err(UgiError::Io(_))));
+        #[cfg(unix)]
+        assert!(matches!(ExternalEngine::spawn("true"), Err(UgiError::Handshake)));
+    }
+}
@@ -0,0 +1,418 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::ai::{Budget, Expectiminimax, Mcts, Source, WinEstimator};
+use crate::clock::{SystemClock, TimeControl};
+use crate::dice::SeededDice;
+use crate::fair_dice::{FairRoll, Secret};
+use crate::game::{GameState, Player};
+use crate::moves::MoveError;
+use crate::record::GameRecord;
+use crate::rules::{RuleSet, Variant};
+
+// JavaScript-facing wrapper around the native GameState, exported to JS as `GameState`
+#[wasm_bindgen(js_name = GameState)]
+pub struct WasmGameState {
+    game: GameState,
+    // JS functions can't be Rust observers, which must be Send, so they're handed the
+    // game's queued events after each call that plays something
+    listeners: Vec<(u32, js_sys::Function)>,
+    next_listener: u32,
+    estimator: WinEstimator,  // For win_probability, which the page asks after every change
+}
+
+#[wasm_bindgen(js_class = GameState)]
+impl WasmGameState {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmGameState {
+        WasmGameState::from(GameState::default())
+    }
+
+    pub fn with_variant(variant: Variant) -> WasmGameState {
+        WasmGameState::from(GameState::new(RuleSet::for_variant(variant)))
+    }
+
+    pub fn with_seed(variant: Variant, seed: u64) -> WasmGameState {
+        WasmGameState::from(GameState::with_dice(RuleSet::for_variant(variant), Box::new(SeededDice::new(seed))))
+    }
+
+    // Rebuilds a position from position_key, throwing if the key isn't a valid position
+    pub fn from_position_key(variant: Variant, key: u64) -> Result<WasmGameState, JsError> {
+        GameState::from_position_key(RuleSet::for_variant(variant), key)
+            .map(WasmGameState::from)
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    // Loads a position such as "20 7/0 7/0 L -", throwing if it can't be read
+    pub fn from_notation(notation: &str, variant: Variant) -> Result<WasmGameState, JsError> {
+        GameState::from_notation(RuleSet::for_variant(variant), notation)
+            .map(WasmGameState::from)
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    // Replays a game record, throwing if it can't be read or an action is illegal
+    pub fn replay_record(record: &str) -> Result<WasmGameState, JsError> {
+        record.parse::<GameRecord>()
+            .and_then(|record| record.replay())
+            .map(WasmGameState::from)
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn variant(&self) -> Variant {
+        self.game.rules().variant
+    }
+
+    // Seed of the dice stream, if the rolls can be reproduced
+    #[wasm_bindgen(getter)]
+    pub fn dice_seed(&self) -> Option<u64> {
+        self.game.dice_source().seed().map(|seeded| seeded.seed)
+    }
+
+    // Number of random values drawn from the seeded dice stream so far
+    #[wasm_bindgen(getter)]
+    pub fn dice_position(&self) -> Option<u64> {
+        self.game.dice_source().seed().map(|seeded| seeded.position)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn current_player(&self) -> Player {
+        self.game.current_player()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dice_value(&self) -> u8 {
+        self.game.dice_value()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn game_over(&self) -> bool {
+        self.game.game_over()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn winner(&self) -> Option<Player> {
+        self.game.winner()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn light_pieces_start(&self) -> u8 {
+        self.game.light_pieces_start()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dark_pieces_start(&self) -> u8 {
+        self.game.dark_pieces_start()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn light_pieces_off(&self) -> u8 {
+        self.game.light_pieces_off()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dark_pieces_off(&self) -> u8 {
+        self.game.dark_pieces_off()
+    }
+
+    // Compact key of the current position, as a BigInt
+    #[wasm_bindgen(getter)]
+    pub fn position_key(&self) -> Result<u64, JsError> {
+        self.game.position_key().map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn zobrist_hash(&self) -> u64 {
+        self.game.zobrist_hash()
+    }
+
+    pub fn to_notation(&self) -> String {
+        self.game.to_notation()
+    }
+
+    // Game record of the game so far; empty names and date are left out
+    pub fn export_record(&self, light: &str, dark: &str, date: &str) -> String {
+        let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+        let mut record = self.game.record();
+        record.light = non_empty(light);
+        record.dark = non_empty(dark);
+        record.date = non_empty(date);
+        record.to_string()
+    }
+
+    pub fn get_board(&self) -> JsValue {
+        // Convert board to a format JavaScript can understand
+        // 0 = empty, 1 = light piece, 2 = dark piece
+        let board_array: Vec<u8> = self.game.board().iter().map(|sq| piece_type(sq.piece)).collect();
+        serde_wasm_bindgen::to_value(&board_array).unwrap()
+    }
+
+    pub fn roll_dice(&mut self) -> Result<u8, JsValue> {
+        let roll = self.game.roll_dice();
+        self.notify();
+        roll.map_err(|error| JsMoveError::from(error).into())
+    }
+
+    // Probability of rolling each value with this rule set's dice, indexed by value
+    pub fn dice_probabilities(&self) -> Vec<f64> {
+        self.game.rules().dice.probabilities()
+    }
+
+    pub fn board_index_to_path(&self, square_index: usize, player: Player) -> Option<usize> {
+        self.game.board_index_to_path(square_index, player)
+    }
+
+    // Board indices of the player's route, in path order starting at path 1
+    pub fn get_route(&self, player: Player) -> JsValue {
+        serde_wasm_bindgen::to_value(self.game.rules().route(player).squares()).unwrap()
+    }
+
+    // Grid position of each square, as [{ row, col, rosette }] indexed by board index
+    pub fn get_layout(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self.game.rules().layout().cells()).unwrap()
+    }
+
+    pub fn can_move(&self, from_path_pos: usize) -> bool {
+        self.game.can_move(from_path_pos)
+    }
+
+    pub fn get_valid_moves(&self) -> JsValue {
+        let moves: Vec<usize> = self.game.legal_moves().iter().map(|mv| mv.from).collect();
+        serde_wasm_bindgen::to_value(&moves).unwrap()
+    }
+
+    // Legal moves as objects: { player, from, to, from_index, to_index, captures, rosette, bears_off }
+    pub fn get_legal_moves(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.game.legal_moves()).unwrap()
+    }
+
+    // Returns { move, extra_turn, winner }, or throws { kind, message } if the move is illegal
+    pub fn make_move(&mut self, from_path_pos: usize) -> Result<JsValue, JsValue> {
+        let result = self.game.make_move(from_path_pos);
+        self.notify();
+        match result {
+            Ok(outcome) => Ok(serde_wasm_bindgen::to_value(&outcome).unwrap()),
+            Err(error) => Err(JsMoveError::from(error).into()),
+        }
+    }
+
+    // Expectiminimax's choice for the current roll, searching depth plies ahead:
+    // { move, value, nodes }, or undefined before rolling or when nothing can move
+    pub fn best_move(&self, depth: u32) -> JsValue {
+        serde_wasm_bindgen::to_value(&Expectiminimax::new(depth).search(&self.game)).unwrap()
+    }
+
+    // Monte Carlo tree search's choice for the current roll after the given number of
+    // playouts: { move, moves: [{ move, visits, win_rate }], iterations }, or undefined
+    pub fn mcts_move(&self, iterations: u32) -> JsValue {
+        serde_wasm_bindgen::to_value(&Mcts::new(Budget::Iterations(iterations)).search(&self.game)).unwrap()
+    }
+
+    // As mcts_move, searching for the given number of milliseconds
+    pub fn mcts_move_timed(&self, millis: f64) -> JsValue {
+        serde_wasm_bindgen::to_value(&Mcts::new(Budget::Millis(millis)).search(&self.game)).unwrap()
+    }
+
+    // Each player's chance of winning, estimated by searching depth plies ahead:
+    // { light, dark, source }
+    pub fn win_probability(&mut self, depth: u32) -> JsValue {
+        if self.estimator.search.as_ref().map(|search| search.depth) != Some(depth) {
+            self.estimator = WinEstimator::new();
+            self.estimator.search = Some(Expectiminimax::new(depth));
+        }
+        let estimate = self.estimator.estimate(&self.game);
+        let chances = JsWinProbability {
+            light: estimate.for_player(Player::Light),
+            dark: estimate.for_player(Player::Dark),
+            source: estimate.source,
+        };
+        serde_wasm_bindgen::to_value(&chances).unwrap()
+    }
+
+    pub fn pass_turn(&mut self) {
+        self.game.pass_turn();
+        self.notify();
+    }
+
+    // Calls callback with each game event, e.g. { type: "PieceMoved", player, from, to },
+    // once the call that caused it has finished. Returns an id for unsubscribe
+    pub fn subscribe(&mut self, callback: js_sys::Function) -> u32 {
+        self.next_listener += 1;
+        self.listeners.push((self.next_listener, callback));
+        self.next_listener
+    }
+
+    pub fn unsubscribe(&mut self, id: u32) -> bool {
+        let count = self.listeners.len();
+        self.listeners.retain(|(listener, _)| *listener != id);
+        self.listeners.len() != count
+    }
+
+    pub fn reset(&mut self) {
+        self.game.reset();
+    }
+
+    // Starts both players' clocks, e.g. set_time_control(180000, 2000, 0) for 3 minutes
+    // with a 2 second increment. The clocks carry over to reset games
+    pub fn set_time_control(&mut self, initial_ms: u32, increment_ms: u32, delay_ms: u32) {
+        let control = TimeControl {
+            initial_ms: initial_ms.into(),
+            increment_ms: increment_ms.into(),
+            delay_ms: delay_ms.into(),
+        };
+        self.game.set_time_control(control, Box::new(SystemClock));
+    }
+
+    // { initial_ms, increment_ms, delay_ms }, or undefined for an untimed game
+    #[wasm_bindgen(getter)]
+    pub fn time_control(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.game.time_control()).unwrap()
+    }
+
+    pub fn remaining_millis(&self, player: Player) -> Option<f64> {
+        self.game.remaining_millis(player)
+    }
+
+    // Call this every so often during a timed game: true once the player to move has run
+    // out of time, which ends the game
+    pub fn check_time(&mut self) -> bool {
+        let flagged = self.game.check_time();
+        self.notify();
+        flagged
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn can_undo(&self) -> bool {
+        self.game.can_undo()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn can_redo(&self) -> bool {
+        self.game.can_redo()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.game.undo().is_some()
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.game.redo().is_some()
+    }
+
+    // Actions played so far, e.g. [{ type: "Roll", player, value }, { type: "Move", mv }, { type: "Pass", player }]
+    pub fn get_history(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.game.history()).unwrap()
+    }
+
+    pub fn get_square_info(&self, square_index: usize) -> JsValue {
+        // Returns: [piece_type, is_valid_move, is_rosette]
+        // piece_type: 0=empty, 1=light, 2=dark
+        // is_valid_move: 0=false, 1=true
+        // is_rosette: 0=false, 1=true
+        let square = self.game.board()[square_index];
+        let is_valid_move = self.game.legal_moves().iter().any(|mv| mv.from_index == Some(square_index)) as u8;
+
+        serde_wasm_bindgen::to_value(&[piece_type(square.piece), is_valid_move, square.rosette as u8]).unwrap()
+    }
+
+    pub fn is_rosette(&self, square_index: usize) -> bool {
+        self.game.board().get(square_index).is_some_and(|square| square.rosette)
+    }
+
+    pub fn count_pieces_on_board(&self, player: Player) -> u8 {
+        self.game.count_pieces_on_board(player)
+    }
+
+    pub fn get_status_message(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.game.status_message()).unwrap()
+    }
+
+    pub fn get_player_name(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self.game.current_player().name()).unwrap()
+    }
+}
+
+impl Default for WasmGameState {
+    fn default() -> Self {
+        WasmGameState::new()
+    }
+}
+
+impl WasmGameState {
+    // A listener that throws doesn't keep the others from hearing the event
+    fn notify(&mut self) {
+        for event in self.game.drain_events() {
+            let event = serde_wasm_bindgen::to_value(&event).unwrap();
+            for (_, listener) in &self.listeners {
+                let _ = listener.call1(&JsValue::NULL, &event);
+            }
+        }
+    }
+}
+
+impl From<GameState> for WasmGameState {
+    // Events from building the game, such as replaying a record, aren't news to anyone
+    fn from(mut game: GameState) -> Self {
+        game.drain_events();
+        WasmGameState { game, listeners: Vec::new(), next_listener: 0, estimator: WinEstimator::new() }
+    }
+}
+
+// Chances returned to JS by win_probability, e.g. { light: 0.6, dark: 0.4, source: "Search" }
+#[derive(Serialize)]
+struct JsWinProbability {
+    light: f64,
+    dark: f64,
+    source: Source,
+}
+
+// Error thrown to JS, e.g. { kind: "BlockedByOwnPiece", message: "Your own piece is in the way" }
+#[derive(Serialize)]
+struct JsMoveError {
+    kind: MoveError,
+    message: String,
+}
+
+impl From<MoveError> for JsMoveError {
+    fn from(error: MoveError) -> Self {
+        JsMoveError { kind: error, message: error.to_string() }
+    }
+}
+
+impl From<JsMoveError> for JsValue {
+    fn from(error: JsMoveError) -> Self {
+        serde_wasm_bindgen::to_value(&error).unwrap()
+    }
+}
+
+fn piece_type(piece: Option<Player>) -> u8 {
+    match piece {
+        Some(Player::Light) => 1,
+        Some(Player::Dark) => 2,
+        None => 0,
+    }
+}
+
+// Commit-reveal dice for online play (see fair_dice.rs). A fresh secret as hex
+#[wasm_bindgen]
+pub fn random_dice_secret() -> String {
+    Secret::random().to_hex()
+}
+
+#[wasm_bindgen]
+pub fn dice_commitment(secret: &str, round: u64) -> Result<String, JsError> {
+    let secret = Secret::from_hex(secret).map_err(|error| JsError::new(&error.to_string()))?;
+    Ok(secret.commitment(round).to_hex())
+}
+
+// Checks a finished roll sent by a server, returning its value
+#[wasm_bindgen]
+pub fn verify_fair_roll(roll: JsValue) -> Result<u8, JsError> {
+    let roll: FairRoll = serde_wasm_bindgen::from_value(roll).map_err(|error| JsError::new(&error.to_string()))?;
+    roll.verify().map_err(|error| JsError::new(&error.to_string()))
+}
+
+#[wasm_bindgen]
+pub fn init() {
+    console_error_panic_hook::set_once();
+}